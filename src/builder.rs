@@ -0,0 +1,519 @@
+/*
+    no_packsaran — Generator of images to defeat
+    Copyright (C) 2024  Eldred “ISSOtm” Habert <agpl@eldred.fr>
+
+    This program is free software: you can redistribute it and/or modify
+    it under the terms of the GNU Affero General Public License as published by
+    the Free Software Foundation, either version 3 of the License, or
+    (at your option) any later version.
+
+    This program is distributed in the hope that it will be useful,
+    but WITHOUT ANY WARRANTY; without even the implied warranty of
+    MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+    GNU Affero General Public License for more details.
+
+    You should have received a copy of the GNU Affero General Public License
+    along with this program.  If not, see <https://www.gnu.org/licenses/>.
+*/
+
+//! A more discoverable way of generating images than [`gen_image`][crate::gen_image]'s many arguments.
+
+use std::{
+    borrow::Borrow,
+    io::{IsTerminal, Write},
+    num::NonZeroUsize,
+};
+
+use plumers::{image::ImageDest, prelude::*};
+
+use crate::{ProtoPalette, TileFill};
+
+/// Generates tiles that contain the specified colours and none else.
+///
+/// Everything but the palette and the tiles has a default: 8x8 tiles, in a single column, one frame, and a PNG.
+///
+/// The tiles are iterated through twice, so they can be generated lazily (see e.g. [`any_fit_tiles`][crate::any_fit_tiles]).
+/// Tiles without any colours are rejected with [`std::io::ErrorKind::InvalidInput`].
+///
+/// The tiles are laid out in a grid [`columns`][Self::columns] tiles wide (or [two of them][Self::side_by_side]),
+/// as many [`rows`][Self::rows] tall as needed unless told otherwise;
+/// any unused cells are left with the [`background`][Self::background] colour (0 by default).
+///
+/// The image is made of [`frames`][Self::frames] frames, each of which rotates the tiles by one more position than the last:
+/// frame `f`'s first tile is tile `f`, wrapping around. (Only some formats support more than one frame.)
+///
+/// Tiles can also be [`annotate`][Self::annotate]d with their index, and the whole image [`scale`][Self::scale]d up,
+/// e.g. for presentations.
+///
+/// Note that colour 0 is *not* transparent: the alpha mode only says how to interpret the palette's alpha bytes,
+/// and the palettes generated by this crate are fully opaque (alpha `0xFF`); clear a colour's alpha byte to make it transparent.
+/// Packers that reserve index 0 for transparency (e.g. for Game Boy sprites) do so by convention, not because of the image.
+///
+/// ```
+/// use no_packsaran::{build_palette, plumers::prelude::*, ImageBuilder, PaletteStyle, ProtoPalette};
+///
+/// let palette = build_palette(4, PaletteStyle::default());
+/// let tiles = [ProtoPalette::new(vec![0, 1]), ProtoPalette::new(vec![2, 3])];
+/// let image = ImageBuilder::new()
+///     .tile_size(8, 8)
+///     .palette(&palette)
+///     .tiles(&tiles)
+///     .columns(2)
+///     .build_in_memory()?;
+/// assert_eq!((image.width(), image.height()), (16, 8));
+/// # Ok::<(), std::io::Error>(())
+/// ```
+#[derive(Debug, Clone)]
+pub struct ImageBuilder<'a, Tiles> {
+    tile_width: usize,
+    tile_height: usize,
+    columns: usize,
+    nb_frames: usize,
+    palette: &'a [Rgb32],
+    tiles: Tiles,
+    fill: TileFill,
+    format: ImageFormat,
+    background: u8,
+    label: Option<u8>,
+    scale: usize,
+    progress: bool,
+    nb_left: Option<usize>,
+    rows: Option<usize>,
+    truecolor: bool,
+}
+
+impl ImageBuilder<'static, [ProtoPalette; 0]> {
+    pub fn new() -> Self {
+        Self {
+            tile_width: 8,
+            tile_height: 8,
+            columns: 1,
+            nb_frames: 1,
+            palette: &[],
+            tiles: [],
+            fill: TileFill::default(),
+            format: ImageFormat::Png,
+            background: 0,
+            label: None,
+            scale: 1,
+            progress: false,
+            nb_left: None,
+            rows: None,
+            truecolor: false,
+        }
+    }
+}
+
+impl Default for ImageBuilder<'static, [ProtoPalette; 0]> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<'a, Tiles> ImageBuilder<'a, Tiles> {
+    /// How large each tile is, in pixels.
+    pub fn tile_size(self, width: usize, height: usize) -> Self {
+        Self {
+            tile_width: width,
+            tile_height: height,
+            ..self
+        }
+    }
+
+    /// How many tiles wide the image is.
+    pub fn columns(self, columns: usize) -> Self {
+        assert_ne!(columns, 0, "There must be at least one column of tiles!");
+        Self { columns, ..self }
+    }
+
+    /// How many tiles tall the image is, padding it with [`background`][Self::background] below the tiles.
+    /// Defaults to as few rows as the tiles need; asking for fewer than that is an error.
+    pub fn rows(self, rows: usize) -> Self {
+        Self {
+            rows: Some(rows),
+            ..self
+        }
+    }
+
+    /// How many frames the image has.
+    pub fn frames(self, nb_frames: usize) -> Self {
+        assert_ne!(nb_frames, 0, "There must be at least one frame!");
+        Self { nb_frames, ..self }
+    }
+
+    /// The colours that the tiles' indices refer to.
+    pub fn palette<'p>(self, palette: &'p [Rgb32]) -> ImageBuilder<'p, Tiles> {
+        ImageBuilder {
+            tile_width: self.tile_width,
+            tile_height: self.tile_height,
+            columns: self.columns,
+            nb_frames: self.nb_frames,
+            palette,
+            tiles: self.tiles,
+            fill: self.fill,
+            format: self.format,
+            background: self.background,
+            label: self.label,
+            scale: self.scale,
+            progress: self.progress,
+            nb_left: self.nb_left,
+            rows: self.rows,
+            truecolor: self.truecolor,
+        }
+    }
+
+    /// Which colours each tile uses, in order.
+    pub fn tiles<T>(self, tiles: T) -> ImageBuilder<'a, T> {
+        ImageBuilder {
+            tile_width: self.tile_width,
+            tile_height: self.tile_height,
+            columns: self.columns,
+            nb_frames: self.nb_frames,
+            palette: self.palette,
+            tiles,
+            fill: self.fill,
+            format: self.format,
+            background: self.background,
+            label: self.label,
+            scale: self.scale,
+            progress: self.progress,
+            nb_left: self.nb_left,
+            rows: self.rows,
+            truecolor: self.truecolor,
+        }
+    }
+
+    /// How each tile's colours get spread across its pixels.
+    pub fn fill(self, fill: TileFill) -> Self {
+        Self { fill, ..self }
+    }
+
+    /// Which colour the pixels not covered by any tile get.
+    pub fn background(self, background: u8) -> Self {
+        Self { background, ..self }
+    }
+
+    /// Draws each tile's index in its top-left corner, using this colour.
+    ///
+    /// The label's pixels replace the tile's, so the label's colour gets added to every tile's proto-palette,
+    /// and some of the tile's own colours may get covered up: the image then no longer defeats anything!
+    /// Digits are 3x5 pixels, and those that don't fit in the tile are cut off.
+    pub fn annotate(self, label: u8) -> Self {
+        Self {
+            label: Some(label),
+            ..self
+        }
+    }
+
+    /// Makes each pixel a square of `scale` by `scale` pixels, so that the tiles are easier to see.
+    ///
+    /// The tiles' proto-palettes are unaffected, though tiles of a different size may throw off some packers.
+    pub fn scale(self, scale: usize) -> Self {
+        assert_ne!(scale, 0, "The image must be scaled by at least 1!");
+        Self { scale, ..self }
+    }
+
+    /// Reports on stderr how many tiles have been drawn so far, for large images that take a while.
+    ///
+    /// Nothing is printed if stderr isn't a terminal, so that logs don't fill up with progress lines.
+    pub fn progress(self, progress: bool) -> Self {
+        Self { progress, ..self }
+    }
+
+    /// Lays out the first `nb_left` tiles and the rest as two separate grids, each [`columns`][Self::columns] tiles wide,
+    /// side by side with a column of [`background`][Self::background] between them; e.g. to compare two strategies.
+    ///
+    /// Both halves index the same palette, so the tiles must already keep their colours apart if that matters
+    /// (as [`defeat_combined`][crate::defeat_combined] does). Frames rotate the tiles across both halves.
+    pub fn side_by_side(self, nb_left: usize) -> Self {
+        Self {
+            nb_left: Some(nb_left),
+            ..self
+        }
+    }
+
+    /// Stores each pixel's actual colour instead of its index (e.g. a truecolour PNG), for tools that can't read paletted images.
+    ///
+    /// This loses which colours are "the same" as far as the indices go, which is the whole point of the tiles;
+    /// so this is only meant for looking at them. [`build_in_memory`][Self::build_in_memory] is unaffected.
+    pub fn truecolor(self, truecolor: bool) -> Self {
+        Self { truecolor, ..self }
+    }
+
+    /// Which format the image will be stored as.
+    pub fn format(self, format: ImageFormat) -> Self {
+        Self { format, ..self }
+    }
+}
+
+impl<Tiles: IntoIterator<Item = Tile>, Tile: Borrow<ProtoPalette>> ImageBuilder<'_, Tiles>
+where
+    Tiles::IntoIter: Clone,
+{
+    /// Generates the image, without storing it anywhere.
+    pub fn build_in_memory(self) -> std::io::Result<PalettedImage32> {
+        let Self {
+            tile_width,
+            tile_height,
+            columns,
+            nb_frames,
+            palette,
+            tiles,
+            fill,
+            format,
+            background,
+            label,
+            scale,
+            progress,
+            nb_left,
+            rows: min_rows,
+            truecolor: _, // Only used when storing.
+        } = self;
+        if usize::from(background) >= palette.len() {
+            return Err(std::io::Error::new(
+                std::io::ErrorKind::InvalidInput,
+                format!(
+                    "Background colour {background} is not in the palette of {} colours",
+                    palette.len()
+                ),
+            ));
+        }
+        if let Some(label) = label.filter(|&label| usize::from(label) >= palette.len()) {
+            return Err(std::io::Error::new(
+                std::io::ErrorKind::InvalidInput,
+                format!(
+                    "Label colour {label} is not in the palette of {} colours",
+                    palette.len()
+                ),
+            ));
+        }
+
+        // Make a first pass to know how large the image must be; this allows lazily generating the tiles.
+        // This is also a good opportunity to reject tiles whose colours can't all be drawn: empty ones, as they can't
+        // have any pixels, and ones with more colours than pixels, as some of their colours would go missing.
+        let tiles = tiles.into_iter();
+        let mut nb_tiles = 0usize;
+        let nb_pixels = tile_width * tile_height;
+        for (i, tile) in tiles.clone().enumerate() {
+            let tile = tile.borrow();
+            if tile.is_empty() {
+                return Err(std::io::Error::new(
+                    std::io::ErrorKind::InvalidInput,
+                    format!("Tile #{i} has no colours"),
+                ));
+            }
+            if tile.len() > nb_pixels {
+                return Err(std::io::Error::new(
+                    std::io::ErrorKind::InvalidInput,
+                    format!(
+                        "Tile #{i} has {} colours, more than its {tile_width}x{tile_height} pixels",
+                        tile.len()
+                    ),
+                ));
+            }
+            nb_tiles += 1;
+        }
+        // Where the `i`th tile drawn goes, in tiles; the right half starts after the gap column.
+        let position = |i: usize| match nb_left {
+            Some(nb_left) if i >= nb_left => {
+                let i = i - nb_left;
+                (columns + 1 + i % columns, i / columns)
+            }
+            _ => (i % columns, i / columns),
+        };
+        let (columns, rows) = match nb_left {
+            Some(nb_left) => (
+                columns * 2 + 1,
+                nb_left
+                    .min(nb_tiles)
+                    .div_ceil(columns)
+                    .max(nb_tiles.saturating_sub(nb_left).div_ceil(columns)),
+            ),
+            None => (columns, nb_tiles.div_ceil(columns)),
+        };
+        let rows = match min_rows {
+            Some(min_rows) if min_rows < rows => {
+                return Err(std::io::Error::new(
+                    std::io::ErrorKind::InvalidInput,
+                    format!("{nb_tiles} tiles need {rows} rows, not {min_rows}"),
+                ))
+            }
+            Some(min_rows) => min_rows,
+            None => rows,
+        };
+
+        let mut img = PalettedImage32::new_zeroed(
+            format,
+            AlphaMode::ZeroIsTransparent, // The conventional meaning, which is what the palettes' `0xFF`s assume.
+            nb_frames,
+            tile_width * columns,
+            tile_height * rows,
+            palette.iter().copied(),
+        )
+        .map_err(|len| {
+            std::io::Error::new(
+                std::io::ErrorKind::InvalidInput,
+                format!("Palettes must have between 1 and 256 colours, not {len}"),
+            )
+        })?;
+        let mut progress = (progress && std::io::stderr().is_terminal())
+            .then(|| Progress::new(nb_tiles * nb_frames));
+        for frame_idx in 0..nb_frames {
+            let mut frame = img.frame_mut(frame_idx);
+            if background != 0 {
+                // The tiles will overwrite the background where they are, so this is simpler than finding the gaps between them.
+                for y in 0..tile_height * rows {
+                    for x in 0..tile_width * columns {
+                        frame[(x, y)] = background;
+                    }
+                }
+            }
+            // Each tile keeps its own index for filling, so that it looks the same in every frame, merely moving around.
+            let shifted = tiles
+                .clone()
+                .enumerate()
+                .cycle()
+                .skip(frame_idx % nb_tiles.max(1))
+                .take(nb_tiles);
+            for (i, (tile_idx, tile)) in shifted.enumerate() {
+                let tile = tile.borrow().colors();
+                let slots = fill.slots(tile_idx, tile.len(), tile_width, tile_height);
+                let (column, row) = position(i);
+                for y in 0..tile_height {
+                    let dest_y = row * tile_height + y;
+                    for x in 0..tile_width {
+                        let dest_x = column * tile_width + x;
+                        frame[(dest_x, dest_y)] = tile[slots[x + y * tile_width]];
+                    }
+                }
+                if let Some(progress) = &mut progress {
+                    progress.tick();
+                }
+
+                if let Some(label) = label {
+                    let digits = tile_idx.to_string();
+                    for (d, digit) in digits.bytes().enumerate() {
+                        let glyph = DIGITS[usize::from(digit - b'0')];
+                        for (y, glyph_row) in glyph.iter().enumerate().take(tile_height) {
+                            // Digits are separated by a column of pixels.
+                            for x in (0..3).filter(|x| d * 4 + x < tile_width) {
+                                if glyph_row & (0b100 >> x) != 0 {
+                                    frame[(
+                                        column * tile_width + d * 4 + x,
+                                        row * tile_height + y,
+                                    )] = label;
+                                }
+                            }
+                        }
+                    }
+                }
+            }
+        }
+
+        if let Some(progress) = progress {
+            progress.finish();
+        }
+
+        Ok(if scale == 1 {
+            img
+        } else {
+            upscale(&img, scale)
+        })
+    }
+
+    /// Generates the image, and writes it to `dest` (e.g. a `&Path`, or a [`plumers::image::Output`] wrapping any `Write`r).
+    ///
+    /// Returns how many bytes were written.
+    pub fn build_and_store<Dest: ImageDest>(self, dest: Dest) -> std::io::Result<NonZeroUsize> {
+        let truecolor = self.truecolor;
+        let img = self.build_in_memory()?;
+        if truecolor {
+            DirectImage32::from_fn(
+                img.format(),
+                img.alpha_mode(),
+                img.nb_frames(),
+                img.width(),
+                img.height(),
+                |frame_idx, x, y| img.pixel(frame_idx, x, y),
+            )
+            .store(dest)
+        } else {
+            img.store(dest)
+        }
+    }
+}
+
+/// A percentage on stderr, only rewritten when it changes, so that huge images don't spend their time printing.
+struct Progress {
+    nb_done: usize,
+    total: usize,
+    percent: usize,
+}
+
+impl Progress {
+    fn new(total: usize) -> Self {
+        Self {
+            nb_done: 0,
+            total,
+            percent: 0,
+        }
+    }
+
+    fn tick(&mut self) {
+        self.nb_done += 1;
+        let percent = self.nb_done * 100 / self.total;
+        if percent != self.percent || self.nb_done == 1 {
+            self.percent = percent;
+            // Errors writing to stderr can't be reported anywhere anyway.
+            let _ = write!(
+                std::io::stderr().lock(),
+                "\rDrawing tiles: {percent:3}% ({}/{})",
+                self.nb_done,
+                self.total,
+            );
+        }
+    }
+
+    /// Erases the progress line, so that it doesn't get mixed with what gets printed next.
+    fn finish(self) {
+        let _ = write!(std::io::stderr().lock(), "\r\x1b[K");
+    }
+}
+
+/// Nearest-neighbour upscaling: each of the image's pixels becomes a `scale` by `scale` square.
+fn upscale(img: &PalettedImage32, scale: usize) -> PalettedImage32 {
+    let (width, height) = (img.width(), img.height());
+    let mut scaled = PalettedImage32::new_zeroed(
+        img.format(),
+        img.alpha_mode(),
+        img.nb_frames(),
+        width * scale,
+        height * scale,
+        img.palette().iter().copied(),
+    )
+    .expect("The palette was already accepted for the original image");
+    for frame_idx in 0..img.nb_frames() {
+        let frame = img.frame(frame_idx);
+        let mut scaled_frame = scaled.frame_mut(frame_idx);
+        for y in 0..height * scale {
+            for x in 0..width * scale {
+                scaled_frame[(x, y)] = frame[(x / scale, y / scale)];
+            }
+        }
+    }
+    scaled
+}
+
+/// A 3x5 bitmap font for the labels drawn by [`ImageBuilder::annotate`]; each row's 3 low bits are its pixels, left to right.
+const DIGITS: [[u8; 5]; 10] = [
+    [0b111, 0b101, 0b101, 0b101, 0b111],
+    [0b010, 0b110, 0b010, 0b010, 0b111],
+    [0b111, 0b001, 0b111, 0b100, 0b111],
+    [0b111, 0b001, 0b011, 0b001, 0b111],
+    [0b101, 0b101, 0b111, 0b001, 0b001],
+    [0b111, 0b100, 0b111, 0b001, 0b111],
+    [0b111, 0b100, 0b111, 0b101, 0b111],
+    [0b111, 0b001, 0b010, 0b010, 0b010],
+    [0b111, 0b101, 0b111, 0b101, 0b111],
+    [0b111, 0b101, 0b111, 0b001, 0b111],
+];
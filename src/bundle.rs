@@ -0,0 +1,47 @@
+/*
+    no_packsaran — Generator of images to defeat
+    Copyright (C) 2024  Eldred “ISSOtm” Habert <agpl@eldred.fr>
+
+    This program is free software: you can redistribute it and/or modify
+    it under the terms of the GNU Affero General Public License as published by
+    the Free Software Foundation, either version 3 of the License, or
+    (at your option) any later version.
+
+    This program is distributed in the hope that it will be useful,
+    but WITHOUT ANY WARRANTY; without even the implied warranty of
+    MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+    GNU Affero General Public License for more details.
+
+    You should have received a copy of the GNU Affero General Public License
+    along with this program.  If not, see <https://www.gnu.org/licenses/>.
+*/
+
+//! Zip archives holding an image along with its metadata, so that a test case can be distributed as a single file.
+//!
+//! This is behind the `bundle` feature, as it's the only thing that needs the `zip` crate.
+
+use std::{io::Write, path::Path};
+
+use zip::{write::SimpleFileOptions, CompressionMethod, DateTime, ZipWriter};
+
+/// Writes a zip archive to `path`, containing each of the `files` (a name, and its contents).
+///
+/// The archive is reproducible: the files are sorted by name (regardless of the order they are given in),
+/// all dated 1980-01-01 (the earliest date that zip can store), and stored uncompressed,
+/// so that a different compressor (or version thereof) can't change them. PNGs are compressed already anyway.
+pub fn write_bundle(path: &Path, files: &[(&str, &[u8])]) -> std::io::Result<()> {
+    let mut files = files.to_vec();
+    files.sort_unstable_by_key(|&(name, _)| name);
+    let options = SimpleFileOptions::default()
+        .compression_method(CompressionMethod::Stored)
+        .last_modified_time(DateTime::default())
+        .unix_permissions(0o644);
+
+    let mut zip = ZipWriter::new(std::fs::File::create(path)?);
+    for (name, contents) in files {
+        zip.start_file(name, options)?;
+        zip.write_all(contents)?;
+    }
+    zip.finish()?;
+    Ok(())
+}
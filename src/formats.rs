@@ -0,0 +1,582 @@
+/*
+    no_packsaran — Generator of images to defeat
+    Copyright (C) 2024  Eldred “ISSOtm” Habert <agpl@eldred.fr>
+
+    This program is free software: you can redistribute it and/or modify
+    it under the terms of the GNU Affero General Public License as published by
+    the Free Software Foundation, either version 3 of the License, or
+    (at your option) any later version.
+
+    This program is distributed in the hope that it will be useful,
+    but WITHOUT ANY WARRANTY; without even the implied warranty of
+    MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+    GNU Affero General Public License for more details.
+
+    You should have received a copy of the GNU Affero General Public License
+    along with this program.  If not, see <https://www.gnu.org/licenses/>.
+*/
+
+//! The formats that tiles can be output in, and encoders for the "raw" ones that consoles consume directly.
+
+use std::{fmt::Display, str::FromStr};
+
+use plumers::{color::Rgb32, image::ImageFormat};
+
+use crate::{AssignmentError, Attack, Console, ProtoPalette, TileFill};
+
+/// The formats that images can be written in.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum OutputFormat {
+    /// A regular paletted PNG.
+    #[default]
+    Png,
+    /// A truecolour PNG, whose pixels are colours instead of indices; only good for looking at the tiles.
+    RgbPng,
+    /// An animated PNG, which unlike a regular PNG can hold several frames.
+    Apng,
+    /// A GIF, which can also hold several frames.
+    Gif,
+    /// An uncompressed Windows bitmap, for tools that can't be bothered to decode anything.
+    Bmp,
+    /// The Game Boy's monochrome tile format: 1 bit per pixel, e.g. for fonts that get expanded to 2bpp.
+    OneBpp,
+    /// The Game Boy's native tile format: 2 bits per pixel, with interleaved bitplanes.
+    TwoBpp,
+    /// The SNES' native tile format: 4 bits per pixel, as two consecutive 2bpp "halves".
+    FourBpp,
+    /// A C header defining an array of every pixel's colour index.
+    CHeader,
+    /// RGBDS assembly, defining the tiles in 2bpp format.
+    RgbdsAsm,
+}
+
+impl OutputFormat {
+    pub const ALL: [Self; 10] = [
+        Self::Png,
+        Self::RgbPng,
+        Self::Apng,
+        Self::Gif,
+        Self::Bmp,
+        Self::OneBpp,
+        Self::TwoBpp,
+        Self::FourBpp,
+        Self::CHeader,
+        Self::RgbdsAsm,
+    ];
+
+    /// The name by which the format is selected on the command line.
+    pub fn name(self) -> &'static str {
+        match self {
+            Self::Png => "png",
+            Self::RgbPng => "rgb-png",
+            Self::Apng => "apng",
+            Self::Gif => "gif",
+            Self::Bmp => "bmp",
+            Self::OneBpp => "1bpp",
+            Self::TwoBpp => "2bpp",
+            Self::FourBpp => "4bpp",
+            Self::CHeader => "c-header",
+            Self::RgbdsAsm => "rgbds-asm",
+        }
+    }
+
+    /// The extension to give the format's files, which is usually its name.
+    pub fn extension(self) -> &'static str {
+        match self {
+            Self::RgbPng => "png",
+            _ => self.name(),
+        }
+    }
+
+    /// How many colours a single tile can contain in this format, if it's limited at all.
+    pub fn max_tile_colors(self) -> Option<usize> {
+        match self {
+            Self::Png | Self::RgbPng | Self::Apng | Self::Gif | Self::Bmp | Self::CHeader => None,
+            Self::OneBpp => Some(2),
+            Self::TwoBpp | Self::RgbdsAsm => Some(4),
+            Self::FourBpp => Some(16),
+        }
+    }
+
+    /// The image format to store the tiles as, if this is an image format at all (and not a "raw" one).
+    pub fn image_format(self) -> Option<ImageFormat> {
+        match self {
+            Self::Png | Self::RgbPng => Some(ImageFormat::Png),
+            Self::Apng => Some(ImageFormat::Apng),
+            Self::Gif => Some(ImageFormat::Gif),
+            Self::Bmp => Some(ImageFormat::Bmp),
+            Self::OneBpp | Self::TwoBpp | Self::FourBpp | Self::CHeader | Self::RgbdsAsm => None,
+        }
+    }
+
+    /// The size of the hardware tiles that this format is made of, if it has any.
+    ///
+    /// The tiles' width and height must then both be multiples of it: larger tiles are split into several hardware ones.
+    pub fn hardware_tile_size(self) -> Option<usize> {
+        match self {
+            Self::OneBpp | Self::TwoBpp | Self::FourBpp | Self::RgbdsAsm => Some(8),
+            Self::Png | Self::RgbPng | Self::Apng | Self::Gif | Self::Bmp | Self::CHeader => None,
+        }
+    }
+
+    /// Whether the format can hold more than one frame.
+    pub fn supports_frames(self) -> bool {
+        matches!(self, Self::Apng | Self::Gif)
+    }
+}
+
+impl FromStr for OutputFormat {
+    type Err = UnknownFormat;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Self::ALL
+            .into_iter()
+            .find(|format| format.name() == s)
+            .ok_or_else(|| UnknownFormat(s.to_owned()))
+    }
+}
+
+/// Returned when parsing an [`OutputFormat`] whose name we don't recognise.
+#[derive(Debug)]
+pub struct UnknownFormat(String);
+
+impl Display for UnknownFormat {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "Unknown format \"{}\" (expected one of: ", self.0)?;
+        for (i, format) in OutputFormat::ALL.iter().enumerate() {
+            if i != 0 {
+                write!(f, ", ")?;
+            }
+            write!(f, "{}", format.name())?;
+        }
+        write!(f, ")")?;
+        // These are known to the image library, but not usable here.
+        match self.0.to_ascii_lowercase().as_str() {
+            "tga" => write!(f, "; TGA can't be written, but BMP is also uncompressed"),
+            "jpg" | "jpeg" | "pnm" | "ppm" | "pam" => {
+                write!(
+                    f,
+                    "; that format can't store a palette, which the tiles' colour indices rely on"
+                )
+            }
+            _ => Ok(()),
+        }
+    }
+}
+
+/// The orders that the raw formats can list each tile's pixels in.
+///
+/// Image formats always store their pixels row by row, so this only affects the raw ones.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum PixelOrder {
+    /// Row by row, each left to right; which is what consoles expect.
+    #[default]
+    RowMajor,
+    /// Column by column, each top to bottom; i.e. the tiles are transposed.
+    ColumnMajor,
+}
+
+impl PixelOrder {
+    pub const ALL: [Self; 2] = [Self::RowMajor, Self::ColumnMajor];
+
+    /// The name by which the order is selected on the command line.
+    pub fn name(self) -> &'static str {
+        match self {
+            Self::RowMajor => "row",
+            Self::ColumnMajor => "column",
+        }
+    }
+
+    /// The coordinates of the `i`th pixel of the `line`th row or column, depending on the order.
+    fn pixel(self, line: usize, i: usize) -> (usize, usize) {
+        match self {
+            Self::RowMajor => (i, line),
+            Self::ColumnMajor => (line, i),
+        }
+    }
+}
+
+impl FromStr for PixelOrder {
+    type Err = UnknownPixelOrder;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Self::ALL
+            .into_iter()
+            .find(|order| order.name() == s)
+            .ok_or_else(|| UnknownPixelOrder(s.to_owned()))
+    }
+}
+
+/// Returned when parsing a [`PixelOrder`] whose name we don't recognise.
+#[derive(Debug)]
+pub struct UnknownPixelOrder(String);
+
+impl Display for UnknownPixelOrder {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "Unknown pixel order \"{}\" (expected one of: ", self.0)?;
+        for (i, order) in PixelOrder::ALL.iter().enumerate() {
+            if i != 0 {
+                write!(f, ", ")?;
+            }
+            write!(f, "{}", order.name())?;
+        }
+        write!(f, ")")
+    }
+}
+
+/// Encodes tiles in the Game Boy's 1bpp format, so each tile can only have up to 2 colours.
+///
+/// Like with [`encode_2bpp`], each pixel is encoded as the index of its colour within its tile's proto-palette.
+/// Each 8x8 tile thus becomes 8 bytes, one per row; larger tiles are split like with [`encode_2bpp`].
+pub fn encode_1bpp(
+    tile_width: usize,
+    tile_height: usize,
+    tiles: &[ProtoPalette],
+    fill: TileFill,
+    order: PixelOrder,
+) -> Result<Vec<u8>, EncodeError> {
+    encode_planar(tile_width, tile_height, tiles, fill, order, 1, None)
+}
+
+/// Encodes tiles in the Game Boy's 2bpp format.
+///
+/// Since that format stores indices into a 4-colour palette, and not "global" colours,
+/// each pixel is encoded as the index of its colour *within its tile's proto-palette*.
+/// Each 8x8 tile thus becomes 16 bytes; larger tiles are split into 8x8 ones, left to right then top to bottom.
+///
+/// With [`PixelOrder::ColumnMajor`], each pair of bytes encodes a column of pixels (top to bottom) instead of a row,
+/// and larger tiles are split top to bottom then left to right.
+pub fn encode_2bpp(
+    tile_width: usize,
+    tile_height: usize,
+    tiles: &[ProtoPalette],
+    fill: TileFill,
+    order: PixelOrder,
+) -> Result<Vec<u8>, EncodeError> {
+    encode_planar(tile_width, tile_height, tiles, fill, order, 2, None)
+}
+
+/// Encodes tiles in the Game Boy's 2bpp format, for displaying them on a GBC with their optimal palettes.
+///
+/// Unlike with [`encode_2bpp`], each pixel is encoded as the index of its colour *within the optimal palette
+/// that its tile was assigned* (see [`Attack::optimal_assignment`]), so that the hardware shows the right colours
+/// when the palette is selected by [`encode_gbc_attrmap`] out of the ones written by [`encode_gbc_palettes`].
+pub fn encode_gbc_2bpp(
+    tile_width: usize,
+    tile_height: usize,
+    attack: &Attack,
+    fill: TileFill,
+    order: PixelOrder,
+) -> Result<Vec<u8>, EncodeError> {
+    let positions = attack
+        .optimal_assignment()?
+        .into_iter()
+        .zip(&attack.tiles)
+        .enumerate()
+        .map(|(tile, (palette, colors))| {
+            let palette = gbc_palette(tile, palette)?;
+            let optimal = &attack.optimal_palettes[palette];
+            if optimal.len() > GBC_PALETTE_SIZE {
+                return Err(EncodeError::OptimalPaletteSize {
+                    palette,
+                    nb_colors: optimal.len(),
+                });
+            }
+            // The assignment guarantees that every one of the tile's colours is in there.
+            Ok(colors
+                .colors()
+                .iter()
+                .map(|color| optimal.iter().position(|c| c == color).unwrap())
+                .collect())
+        })
+        .collect::<Result<Vec<Vec<usize>>, _>>()?;
+    encode_planar(
+        tile_width,
+        tile_height,
+        &attack.tiles,
+        fill,
+        order,
+        2,
+        Some(&positions),
+    )
+}
+
+/// Encodes tiles in the SNES' 4bpp format.
+///
+/// Like with [`encode_2bpp`], each pixel is encoded as the index of its colour within its tile's proto-palette.
+/// Each 8x8 tile thus becomes 32 bytes: bitplanes 0 and 1 interleaved, then bitplanes 2 and 3 interleaved.
+/// Larger tiles are split like with [`encode_2bpp`].
+pub fn encode_4bpp(
+    tile_width: usize,
+    tile_height: usize,
+    tiles: &[ProtoPalette],
+    fill: TileFill,
+    order: PixelOrder,
+) -> Result<Vec<u8>, EncodeError> {
+    encode_planar(tile_width, tile_height, tiles, fill, order, 4, None)
+}
+
+/// Both the GB's 2bpp format and the SNES' 4bpp format are made of pairs of interleaved bitplanes;
+/// 1bpp is the degenerate case of a lone bitplane, with no other to interleave it with.
+///
+/// Pixels are encoded as the index of their colour within their tile, unless `positions` says which index
+/// each of the tile's colours should be encoded as instead.
+fn encode_planar(
+    tile_width: usize,
+    tile_height: usize,
+    tiles: &[ProtoPalette],
+    fill: TileFill,
+    order: PixelOrder,
+    bpp: usize,
+    positions: Option<&[Vec<usize>]>,
+) -> Result<Vec<u8>, EncodeError> {
+    if !tile_width.is_multiple_of(8) || !tile_height.is_multiple_of(8) {
+        return Err(EncodeError::TileSize {
+            width: tile_width,
+            height: tile_height,
+        });
+    }
+    let max = 1 << bpp;
+
+    let nb_hw_tiles = (tile_width / 8) * (tile_height / 8);
+    let mut data = Vec::with_capacity(tiles.len() * nb_hw_tiles * 8 * bpp);
+    for (i, tile) in tiles.iter().enumerate() {
+        if tile.len() > max {
+            return Err(EncodeError::TooManyColors {
+                tile: i,
+                nb_colors: tile.len(),
+                max,
+            });
+        }
+
+        let mut slots = fill.slots(i, tile.len(), tile_width, tile_height);
+        if let Some(positions) = positions {
+            for slot in &mut slots {
+                *slot = positions[i][*slot];
+            }
+        }
+        // The hardware tiles go along the rows or columns of the tile, like its pixels do.
+        let (across, down) = order.pixel(tile_height / 8, tile_width / 8);
+        for major in 0..down {
+            for minor in 0..across {
+                let (left, top) = order.pixel(major * 8, minor * 8);
+                for plane in (0..bpp).step_by(2) {
+                    for line in 0..8 {
+                        let (mut low, mut high) = (0u8, 0u8);
+                        for offset in 0..8 {
+                            let (x, y) = order.pixel(line, offset);
+                            let slot = slots[left + x + (top + y) * tile_width] >> plane;
+                            low = low << 1 | (slot & 1) as u8;
+                            high = high << 1 | (slot >> 1 & 1) as u8;
+                        }
+                        data.push(low);
+                        if bpp > 1 {
+                            data.push(high);
+                        }
+                    }
+                }
+            }
+        }
+    }
+    Ok(data)
+}
+
+/// Writes a C header containing every pixel's colour index, as a `const uint8_t` array named `array_name`.
+///
+/// The pixels are laid out tile by tile, each tile being row-major like in the PNG, unless `order` says otherwise.
+/// `NUM_TILES` and `NUM_COLORS` are also `#define`d.
+pub fn encode_c_header(
+    tile_width: usize,
+    tile_height: usize,
+    nb_colors: u8,
+    tiles: &[ProtoPalette],
+    fill: TileFill,
+    order: PixelOrder,
+    array_name: &str,
+) -> Vec<u8> {
+    use std::fmt::Write;
+
+    let mut header = String::new();
+    // Writing to a `String` can't fail, so the `fmt::Result`s are ignored.
+    let _ = writeln!(header, "#include <stdint.h>\n");
+    let _ = writeln!(header, "#define NUM_TILES {}", tiles.len());
+    let _ = writeln!(header, "#define NUM_COLORS {nb_colors}\n");
+    let _ = writeln!(header, "const uint8_t {array_name}[] = {{");
+    for (i, tile) in tiles.iter().enumerate() {
+        let _ = writeln!(header, "\t/* Tile #{i} */");
+        let slots = fill.slots(i, tile.len(), tile_width, tile_height);
+        // Rows are as long as the tile is wide, and columns as long as it's tall.
+        let (line_len, nb_lines) = order.pixel(tile_height, tile_width);
+        for line in 0..nb_lines {
+            header.push('\t');
+            for offset in 0..line_len {
+                let (x, y) = order.pixel(line, offset);
+                let _ = write!(header, "{},", tile.colors()[slots[x + y * tile_width]]);
+            }
+            header.push('\n');
+        }
+    }
+    let _ = writeln!(header, "}};");
+
+    header.into_bytes()
+}
+
+/// Writes RGBDS assembly defining the tiles in 2bpp format (see [`encode_2bpp`]).
+///
+/// Each tile gets its own local label under `EvilTiles`, preceded by a comment listing its colours;
+/// tiles larger than 8x8 are made of several 8x8 ones, one after the other.
+pub fn encode_rgbds_asm(
+    tile_width: usize,
+    tile_height: usize,
+    tiles: &[ProtoPalette],
+    fill: TileFill,
+    order: PixelOrder,
+) -> Result<Vec<u8>, EncodeError> {
+    use std::fmt::Write;
+
+    let data = encode_2bpp(tile_width, tile_height, tiles, fill, order)?;
+    let tile_len = (tile_width / 8) * (tile_height / 8) * 16;
+
+    let mut asm = String::from("EvilTiles::\n");
+    // Writing to a `String` can't fail, so the `fmt::Result`s are ignored.
+    for (i, (tile, tile_data)) in tiles.iter().zip(data.chunks(tile_len)).enumerate() {
+        let _ = write!(asm, "\n; Colours:");
+        for color in tile.colors() {
+            let _ = write!(asm, " {color}");
+        }
+        let _ = writeln!(asm, "\n.tile{i}");
+        for row in tile_data.chunks(2) {
+            let _ = writeln!(asm, "\tdb ${:02x}, ${:02x}", row[0], row[1]);
+        }
+    }
+
+    Ok(asm.into_bytes())
+}
+
+/// Encodes a GBC attribute map: one byte per tile, holding which of the optimal palettes it should be displayed with.
+///
+/// The tilemap itself is just the tiles in order, so only the attributes (VRAM bank 1) are worth emitting.
+/// All other attribute bits (bank, flips, priority) are left clear.
+pub fn encode_gbc_attrmap(attack: &Attack) -> Result<Vec<u8>, EncodeError> {
+    attack
+        .optimal_assignment()?
+        .into_iter()
+        .enumerate()
+        .map(|(tile, palette)| gbc_palette(tile, palette).map(|palette| palette as u8))
+        .collect()
+}
+
+/// How many colours each of the GBC's palettes has.
+const GBC_PALETTE_SIZE: usize = 4;
+
+/// Checks that the tile with that index was assigned a palette that the GBC can select.
+fn gbc_palette(tile: usize, palette: usize) -> Result<usize, EncodeError> {
+    // The GBC has 8 background palettes, selected by the attributes' low 3 bits.
+    if palette < 8 {
+        Ok(palette)
+    } else {
+        Err(EncodeError::PaletteIndex { tile, palette })
+    }
+}
+
+/// Encodes the optimal palettes as a GBC `.pal` file (see [`encode_bgr555_palette`]), taking their colours from `palette`.
+///
+/// The optimal palettes are written in order, each padded to 4 colours with black,
+/// so that the palette that [`encode_gbc_attrmap`] selects for a tile is the one it was assigned.
+pub fn encode_gbc_palettes(attack: &Attack, palette: &[Rgb32]) -> Result<Vec<u8>, EncodeError> {
+    let mut colors = Vec::with_capacity(attack.optimal_palettes.len() * GBC_PALETTE_SIZE);
+    for (i, optimal) in attack.optimal_palettes.iter().enumerate() {
+        if optimal.len() > GBC_PALETTE_SIZE {
+            return Err(EncodeError::OptimalPaletteSize {
+                palette: i,
+                nb_colors: optimal.len(),
+            });
+        }
+        colors.extend(optimal.iter().map(|&color| palette[usize::from(color)]));
+        colors.resize((i + 1) * GBC_PALETTE_SIZE, Rgb32(0xFF00_0000));
+    }
+    encode_bgr555_palette(&colors)
+}
+
+/// Encodes colours as a GBC `.pal` file: each one becomes a little-endian BGR555 word, in index order.
+///
+/// Each word's bits are `0bbbbbgggggrrrrr`, i.e. red in bits 0-4 of the first byte,
+/// green straddling both bytes, and blue in bits 2-6 of the second byte; bit 15 is always clear.
+/// The GBC only has room for 32 background colours (8 palettes of 4), so more than that is an error.
+pub fn encode_bgr555_palette(palette: &[Rgb32]) -> Result<Vec<u8>, EncodeError> {
+    // 8 palettes of 4 colours each.
+    const MAX_COLORS: usize = 32;
+    if palette.len() > MAX_COLORS {
+        return Err(EncodeError::TooManyPaletteColors {
+            nb_colors: palette.len(),
+            max: MAX_COLORS,
+        });
+    }
+    Ok(palette
+        .iter()
+        .flat_map(|&color| Console::Gbc.quantize(color).to_le_bytes())
+        .collect())
+}
+
+/// The reasons why tiles may not be encodable in a given format.
+#[derive(Debug)]
+pub enum EncodeError {
+    /// The format only supports tiles made of 8x8 ones.
+    TileSize { width: usize, height: usize },
+    /// The tile with that index uses more colours than the format can represent.
+    TooManyColors {
+        tile: usize,
+        nb_colors: usize,
+        max: usize,
+    },
+    /// None of the optimal palettes can display one of the tiles.
+    NoOptimalPalette(AssignmentError),
+    /// The tile with that index belongs to an optimal palette that the format can't refer to.
+    PaletteIndex { tile: usize, palette: usize },
+    /// The image has more colours than the format can hold.
+    TooManyPaletteColors { nb_colors: usize, max: usize },
+    /// The optimal palette with that index has more colours than a GBC palette can hold.
+    OptimalPaletteSize { palette: usize, nb_colors: usize },
+}
+
+impl From<AssignmentError> for EncodeError {
+    fn from(err: AssignmentError) -> Self {
+        Self::NoOptimalPalette(err)
+    }
+}
+
+impl Display for EncodeError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::TileSize { width, height } => {
+                write!(
+                    f,
+                    "This format requires tiles whose sides are multiples of 8, not {width}x{height}"
+                )
+            }
+            Self::TooManyColors {
+                tile,
+                nb_colors,
+                max,
+            } => write!(
+                f,
+                "Tile #{tile} uses {nb_colors} colours, but this format only allows {max}"
+            ),
+            Self::NoOptimalPalette(err) => err.fmt(f),
+            Self::PaletteIndex { tile, palette } => write!(
+                f,
+                "Tile #{tile} belongs to optimal palette #{palette}, but the GBC only has 8"
+            ),
+            Self::TooManyPaletteColors { nb_colors, max } => write!(
+                f,
+                "The image has {nb_colors} colours, but the GBC only has room for {max}"
+            ),
+            Self::OptimalPaletteSize { palette, nb_colors } => write!(
+                f,
+                "Optimal palette #{palette} has {nb_colors} colours, but GBC palettes only have {GBC_PALETTE_SIZE}"
+            ),
+        }
+    }
+}
+
+impl std::error::Error for EncodeError {}
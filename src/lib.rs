@@ -0,0 +1,2800 @@
+/*
+    no_packsaran — Generator of images to defeat
+    Copyright (C) 2024  Eldred “ISSOtm” Habert <agpl@eldred.fr>
+
+    This program is free software: you can redistribute it and/or modify
+    it under the terms of the GNU Affero General Public License as published by
+    the Free Software Foundation, either version 3 of the License, or
+    (at your option) any later version.
+
+    This program is distributed in the hope that it will be useful,
+    but WITHOUT ANY WARRANTY; without even the implied warranty of
+    MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+    GNU Affero General Public License for more details.
+
+    You should have received a copy of the GNU Affero General Public License
+    along with this program.  If not, see <https://www.gnu.org/licenses/>.
+*/
+
+//! Generation of images that are hard to generate palettes for.
+//!
+//! Each `defeat_*` function produces a list of [`ProtoPalette`]s (the set of colours used by each tile),
+//! which can then be turned into an image with [`gen_image`].
+
+use std::{
+    borrow::Borrow, collections::HashSet, fmt::Display, num::NonZeroUsize, path::Path, str::FromStr,
+};
+
+use palette::{FromColor, Hsl, Lch, Srgb};
+use plumers::{image::ImageDest, prelude::*};
+
+mod builder;
+pub use builder::ImageBuilder;
+mod formats;
+pub use formats::{
+    encode_1bpp, encode_2bpp, encode_4bpp, encode_bgr555_palette, encode_c_header, encode_gbc_2bpp,
+    encode_gbc_attrmap, encode_gbc_palettes, encode_rgbds_asm, EncodeError, OutputFormat,
+    PixelOrder, UnknownFormat, UnknownPixelOrder,
+};
+mod manifest;
+pub use manifest::{read_manifest_file, Job, ManifestError};
+mod packing;
+pub use packing::{
+    minimal_palettes, pack_any_fit, pack_best_fusion, pack_greedy, pack_mixed, pack_optimal,
+    TooManyProtoPalettes, MAX_EXACT_PROTO_PALETTES,
+};
+mod spec;
+pub use spec::{read_spec_file, spec_json, write_spec_json, Spec, SpecFileError};
+#[cfg(feature = "bundle")]
+mod bundle;
+#[cfg(feature = "bundle")]
+pub use bundle::write_bundle;
+
+// Re-exported so that users of the library can name its types without depending on it themselves.
+pub use plumers;
+
+/// The packing algorithms that we know how to defeat.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Strategy {
+    AnyFit,
+    BestFusion,
+    FirstFit,
+    WorstFit,
+    BestFitDecreasing,
+    NextFit,
+    Dsatur,
+    Superfamiconv,
+    OverloadAndRemove,
+    MergeSmallest,
+    Obj,
+    FfdIndexTiebreak,
+    ClusterThenFit,
+    Combined,
+}
+
+impl Strategy {
+    pub const ALL: [Self; 14] = [
+        Self::AnyFit,
+        Self::BestFusion,
+        Self::FirstFit,
+        Self::WorstFit,
+        Self::BestFitDecreasing,
+        Self::NextFit,
+        Self::Dsatur,
+        Self::Superfamiconv,
+        Self::OverloadAndRemove,
+        Self::MergeSmallest,
+        Self::Obj,
+        Self::FfdIndexTiebreak,
+        Self::ClusterThenFit,
+        Self::Combined,
+    ];
+
+    /// The strategies that [`Strategy::Combined`] combines, unless told otherwise.
+    pub const COMBINED_DEFAULT: [Self; 2] = [Self::AnyFit, Self::BestFusion];
+
+    /// The name by which the strategy is selected on the command line.
+    pub fn name(self) -> &'static str {
+        match self {
+            Self::AnyFit => "any_fit",
+            Self::BestFusion => "best_fusion",
+            Self::FirstFit => "first_fit",
+            Self::WorstFit => "worst_fit",
+            Self::BestFitDecreasing => "best_fit_decreasing",
+            Self::NextFit => "next_fit",
+            Self::Dsatur => "dsatur",
+            Self::Superfamiconv => "superfamiconv",
+            Self::OverloadAndRemove => "overload_and_remove",
+            Self::MergeSmallest => "merge_smallest",
+            Self::Obj => "obj",
+            Self::FfdIndexTiebreak => "ffd_index_tiebreak",
+            Self::ClusterThenFit => "cluster_then_fit",
+            Self::Combined => "combined",
+        }
+    }
+
+    /// A one-line summary of what the strategy does, for `--list-strategies`.
+    pub fn description(self) -> &'static str {
+        match self {
+            Self::AnyFit => "Feeds alternating proto-palettes from two disjoint colour sets (section 3.1.2)",
+            Self::BestFusion => "Pairs up tiles whose intersection gets “locked” by two extra colours (section 3.2.2)",
+            Self::FirstFit => "A 4-tile gadget where the first palette with room is the wrong one",
+            Self::WorstFit => "A 4-tile gadget where the emptiest palette is the wrong one",
+            Self::BestFitDecreasing => "Same-sized tiles, so that sorting them doesn't undo their ordering",
+            Self::NextFit => "Alternates between two colour sets, so that no tile fits with the previous one",
+            Self::Dsatur => "A 5-tile gadget whose most constrained tile goes first, so that a lone colour then lands in the wrong palette",
+            Self::Superfamiconv => "Disjoint tiles sized so that first-fit-decreasing needs one more palette than the SNES' 8",
+            Self::OverloadAndRemove => "Disjoint tiles, so that RGBGFX never removes any and falls back to first-fit-decreasing",
+            Self::MergeSmallest => "Small tiles that merge with each other first, and then no longer fit with the large ones",
+            Self::Obj => "Sprite tiles, whose colour 0 is transparent, that first-fit spreads over more palettes than the DMG's 2",
+            Self::FfdIndexTiebreak => "Same-sized tiles, so that first-fit-decreasing's tie-break by lowest colour index picks their order",
+            Self::ClusterThenFit => "Two tiles from different palettes that overlap the most, so that clustering them first strands the rest",
+            Self::Combined => "Several strategies' tiles one after the other (by default any_fit's and best_fusion's), with their colours kept apart",
+        }
+    }
+
+    /// How the strategy's tiles are built step by step, for `--explain`; this mirrors its function's documentation.
+    ///
+    /// Each line is a step (continued on indented lines), with `N` standing for the palette size; there is no trailing newline.
+    pub fn construction(self) -> &'static str {
+        match self {
+            Self::AnyFit => "\
+1. Divide the colours into two palette-sized disjoint sets: even and odd indices.
+2. Make each tile use only colours from either set (never both!), and feed these proto-palettes alternatingly.
+=> The two sets suffice, but greediness generates N palettes made of one proto-palette from each set.",
+            Self::BestFusion => "\
+0. Let A = 0..N be the input alphabet.
+1. Construct the N tiles of N-1 colours each (the (N-1)-combinations of A).
+2. For each pair of those tiles, take their intersection (of N-2 colours), add two \"locking\" colours (N and N+1), and do the same as step 1.
+3. Emit one proto-palette from step 1, then one from step 2, etc.
+=> One palette containing all of A suffices, plus one per intersection with N and N+1 added.",
+            Self::FirstFit => "\
+0. Let X (even indices) and Y (odd indices) be two disjoint sets of N colours.
+1. Emit a tile using the first half of X; it opens palette #1, which still has N/2 free slots.
+2. Emit a tile using all of Y but one colour; it opens palette #2, which has 1 free slot.
+3. Emit a tile using only the last colour of Y; first-fit commits it to palette #1, the first one it tries.
+4. Emit a tile using the second half of X; neither palette has room for it anymore, so a third one gets opened.
+=> X and Y suffice, but first-fit never gets to undo step 3.",
+            Self::WorstFit => "\
+0. Let X (even indices) and Y (odd indices) be two disjoint sets of N colours.
+1. Emit a tile using all of X but one colour; it opens palette #1, which has 1 free slot.
+2. Emit a tile using the first half of Y; it opens palette #2, which has N/2 free slots.
+3. Emit a tile using only the last colour of X; worst-fit commits it to palette #2, the one with the most room.
+4. Emit a tile using the second half of Y; neither palette has room for it anymore, so a third one gets opened.
+=> X and Y suffice, but worst-fit spreads colours away from the palette they belong to.",
+            Self::BestFitDecreasing => "\
+0. Let K = N/2, and X (even indices) and Y (odd indices) be two disjoint sets of N colours.
+1. Make every tile use exactly K colours, so that a stable sort has nothing to reorder.
+2. Emit a tile using the first half of X, then one using the first half of Y; best-fit packs them into palette #1, filling it up.
+3. Do the same with the second halves of X and Y, which fill up palette #2.
+4. Emit a tile bridging both halves of X; neither full palette has all of its colours, so a third one gets opened.
+=> X and Y suffice, but sorting the tiles beforehand doesn't help at all.",
+            Self::NextFit => "\
+0. Let X (even indices) and Y (odd indices) be two disjoint sets of N colours.
+1. Emit a tile using N/2 + 1 colours of X, then one using N/2 colours of Y; together they take up N + 1 colours,
+   so the second one opens a new palette.
+2. Repeat N times, rotating which colours of X and Y get used, so that each tile just barely fails to fit with the previous one.
+=> X and Y suffice, but next-fit never looks back, and generates 2N palettes.",
+            Self::Dsatur => "\
+0. Let X (even indices) and Y (odd indices) be two disjoint sets of N colours.
+1. Emit a tile using the last colour of Y, then one using the last colour of X; neither conflicts with any other tile.
+2. Emit a tile using the first half of Y, then one using the second half.
+3. Emit a tile using all of X but its last colour; it conflicts with both tiles of step 2, so DSATUR packs it first, into palette #1.
+4. The tiles of step 2 are now the most saturated; neither fits in palette #1, so they fill up palette #2 together.
+5. The tiles of step 1 are tied, so they go in order: Y's colour fills up palette #1, and X's then opens a third palette.
+=> X and Y suffice, but DSATUR only checks compatibility pairwise, so it never sees step 5 coming.",
+            Self::Superfamiconv => "\
+0. Let X and Y be two disjoint sets of N colours.
+1. Split X into tiles of N-6, 4, and 2 colours; and Y into tiles of N-7, 5, and 2 colours.
+2. Sorting puts both big tiles first; the N-6 one gets the 5-colour one, leaving 1 slot, and the N-7 one gets the 4-colour one, leaving 3.
+3. Only one of the 2-colour tiles fits in those 3 slots, so the other opens a third palette.
+4. Repeat this with fresh colours, as many times as the SNES has pairs of palettes (4), or as colour indices allow.
+=> Each X and Y suffices as a palette, but first-fit-decreasing needs at least one more.",
+            Self::OverloadAndRemove => "\
+0. Let X and Y be two disjoint sets of N colours; all tiles are disjoint, so RGBGFX never removes any, and falls back to first-fit-decreasing.
+1. Split X into tiles of N-4, 2, and 2 colours; and Y into tiles of N-5, 3, and 2 colours.
+2. Both big tiles go first; the N-4 one gets the 3-colour one, and the N-5 one two of the 2-colour ones, each leaving 1 slot.
+3. The last 2-colour tile opens a third palette, which decanting can't undo.
+4. Repeat this with fresh colours, as many times as the SNES has pairs of palettes (4), or as colour indices allow.
+=> Each X and Y suffices as a palette, but RGBGFX needs at least one more.",
+            Self::MergeSmallest => "\
+0. Let S = N/2 - 1: two small tiles of S colours fit together, but three don't.
+1. Emit a tile of N-S colours, then a disjoint one of S colours: together, they fill up a palette.
+2. Repeat this with fresh colours, as many times (K) as colour indices allow.
+3. Merging two small tiles (2S = N-2 colours) is cheaper than completing a large one (N), so the small tiles get paired up first.
+4. A pair of small tiles fits neither with a large tile, nor with another small one.
+=> K palettes suffice, but the packer needs one more per pair of small tiles.",
+            Self::Obj => "\
+0. Colour 0 is transparent, and in every tile; let X and Y be two disjoint sets of the N-1 other colours.
+1. Emit a tile using the first colour of X, then one using all of Y but its last colour; they fill up palette #1.
+2. Emit a tile using the rest of X; it opens palette #2, which has 1 free slot.
+3. Emit a tile using the last colour of Y; palette #1 is full, so first-fit fills up palette #2 with it.
+4. Emit a tile using the first two colours of X; neither palette has both, so a third one gets opened.
+=> X and Y suffice (each with colour 0), but first-fit needs one more palette than the DMG has for sprites.",
+            Self::FfdIndexTiebreak => "\
+0. Let K = N/2, and X and Y be two disjoint sets of N colours, each split into two halves of K colours.
+1. Make every tile use exactly K colours, so that only the tie-break (lowest colour index first) orders them.
+2. Number the colours so that the halves' lowest colours come in this order: X's first, Y's first, X's second, Y's second.
+3. First-fit then packs the first halves of X and Y into palette #1, and the second halves into palette #2, filling both up.
+4. Emit a tile bridging both halves of X, whose lowest colour comes after all of theirs; neither full palette has all of its colours.
+5. Emit the tiles in the opposite order, so that it's the tie-break, not their order, that sets this up.
+=> X and Y suffice, but first-fit-decreasing needs a third palette.",
+            Self::ClusterThenFit => "\
+0. Let C be a core of (N-1)/2 colours, and X and Y two disjoint sets of the N - (N-1)/2 others: C+X and C+Y are palettes.
+1. Emit a tile using C and the first colour of X, then one using C and the first colour of Y; they overlap by all of C.
+2. Emit a tile using all of X, then one using all of Y; each overlaps the tile of step 1 that shares its first colour by only that colour.
+3. Clustering merges the most overlapping pair first, i.e. both tiles of step 1; their cluster then fits with neither tile of step 2.
+4. The tiles of step 2 don't overlap, and together they have more than N colours, so they stay apart too.
+5. Repeat this with fresh colours, up to 4 times, or as many as colour indices allow.
+=> C+X and C+Y suffice, but the packer is left with three clusters that no two of fit together.",
+            Self::Combined => "\
+1. Run each of the combined strategies, in order.
+2. Shift each attack's colour indices past those of the ones before it, so that no two attacks share any colours.
+3. Emit all tiles, one attack's after the other's.
+=> Each attack's optimal palettes stay valid, and each of the strategies' packers is still defeated.",
+        }
+    }
+
+    /// The smallest palette size that the strategy can generate an image for.
+    pub fn min_palette_size(self) -> usize {
+        match self {
+            Self::AnyFit | Self::NextFit => 2,
+            // These need enough colours to build gadgets out of.
+            Self::BestFusion
+            | Self::FirstFit
+            | Self::Dsatur
+            | Self::WorstFit
+            | Self::BestFitDecreasing
+            | Self::FfdIndexTiebreak => 4,
+            // The core must overlap more than a single colour, yet leave the two other sets too large to fit together.
+            Self::ClusterThenFit => 5,
+            // The gadget's tiles must all have different sizes.
+            Self::Superfamiconv => 14,
+            Self::OverloadAndRemove => 10,
+            // The small tiles must be too large for three of them to fit together.
+            Self::MergeSmallest => 8,
+            // Colour 0 leaves 2 usable ones, just enough for the gadget.
+            Self::Obj => 3,
+            // This depends on which strategies are combined, so it's checked for each of them instead.
+            Self::Combined => 2,
+        }
+    }
+
+    /// The largest palette size that the strategy can generate an image for, since colour indices must fit in a byte.
+    pub fn max_palette_size(self) -> usize {
+        match self {
+            // These use two disjoint sets of N colours, and N must be even.
+            Self::AnyFit
+            | Self::FirstFit
+            | Self::WorstFit
+            | Self::BestFitDecreasing
+            | Self::NextFit
+            | Self::Dsatur
+            | Self::Superfamiconv
+            | Self::OverloadAndRemove
+            | Self::MergeSmallest
+            | Self::FfdIndexTiebreak => 126,
+            // This uses N colours, plus two "locking" ones.
+            Self::BestFusion => 252,
+            // This uses two disjoint sets of N-1 colours, plus the transparent one.
+            Self::Obj => 128,
+            // This uses two sets of N colours, which share (N-1)/2 of them.
+            Self::ClusterThenFit => 169,
+            // This is checked for each of the combined strategies instead, and so is their total.
+            Self::Combined => 252,
+        }
+    }
+
+    fn check_palette_size(self, palette_size: usize) -> Result<(), PaletteTooLarge> {
+        if palette_size < self.min_palette_size() || palette_size > self.max_palette_size() {
+            return Err(PaletteTooLarge {
+                strategy: self,
+                palette_size,
+            });
+        }
+        Ok(())
+    }
+}
+
+impl FromStr for Strategy {
+    type Err = UnknownStrategy;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Self::ALL
+            .into_iter()
+            .find(|strategy| strategy.name() == s)
+            .ok_or_else(|| UnknownStrategy(s.to_owned()))
+    }
+}
+
+/// Returned when parsing a [`Strategy`] whose name we don't recognise.
+#[derive(Debug)]
+pub struct UnknownStrategy(String);
+
+impl Display for UnknownStrategy {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "Unknown strategy \"{}\" (expected one of: ", self.0)?;
+        for (i, strategy) in Strategy::ALL.iter().enumerate() {
+            if i != 0 {
+                write!(f, ", ")?;
+            }
+            write!(f, "{}", strategy.name())?;
+        }
+        write!(f, ")")
+    }
+}
+
+/// Returned when a strategy is asked for palettes larger than it supports (see [`Strategy::max_palette_size`]),
+/// or smaller than it can do anything with (see [`Strategy::min_palette_size`]).
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PaletteTooLarge {
+    pub strategy: Strategy,
+    pub palette_size: usize,
+}
+
+impl Display for PaletteTooLarge {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        if self.palette_size < self.strategy.min_palette_size() {
+            return write!(
+                f,
+                "The {} strategy needs palettes of at least {} colours, not {}",
+                self.strategy.name(),
+                self.strategy.min_palette_size(),
+                self.palette_size
+            );
+        }
+        write!(
+            f,
+            "The {} strategy supports palettes of at most {} colours, not {}",
+            self.strategy.name(),
+            self.strategy.max_palette_size(),
+            self.palette_size
+        )
+    }
+}
+
+impl std::error::Error for PaletteTooLarge {}
+
+/// The set of colours that a single tile uses.
+///
+/// Packing algorithms only ever see these, so this is what the strategies are really made of.
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Hash, Default)]
+pub struct ProtoPalette(Vec<u8>);
+
+impl ProtoPalette {
+    pub fn new(colors: Vec<u8>) -> Self {
+        Self(colors)
+    }
+
+    /// The colour indices, in the order that the tile's pixels cycle through them.
+    pub fn colors(&self) -> &[u8] {
+        &self.0
+    }
+
+    pub fn into_colors(self) -> Vec<u8> {
+        self.0
+    }
+
+    pub fn len(&self) -> usize {
+        self.0.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+
+    pub fn contains(&self, color: u8) -> bool {
+        self.0.contains(&color)
+    }
+}
+
+impl From<Vec<u8>> for ProtoPalette {
+    fn from(colors: Vec<u8>) -> Self {
+        Self(colors)
+    }
+}
+
+impl FromIterator<u8> for ProtoPalette {
+    fn from_iter<I: IntoIterator<Item = u8>>(iter: I) -> Self {
+        Self(iter.into_iter().collect())
+    }
+}
+
+impl AsRef<[u8]> for ProtoPalette {
+    fn as_ref(&self) -> &[u8] {
+        &self.0
+    }
+}
+
+/// The output of a strategy: which tiles to generate, and how they *should* be packed.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Attack {
+    /// The colours of each tile, in the order they should be fed to the packing algorithm.
+    pub tiles: Vec<ProtoPalette>,
+    /// How many colours the tiles use, in total. (All colour indices are below this.)
+    pub nb_colors: u8,
+    /// A minimal set of palettes that can display all of the tiles.
+    pub optimal_palettes: Vec<Vec<u8>>,
+}
+
+impl Attack {
+    /// How many palettes the tiles actually need, which greedy algorithms should exceed.
+    pub fn nb_optimal_palettes(&self) -> usize {
+        self.optimal_palettes.len()
+    }
+
+    /// The colours below [`nb_colors`][Self::nb_colors] that none of the tiles use, in increasing order.
+    ///
+    /// They'd still take up room in the image's palette, which may confuse packers that don't check; so this should be empty.
+    pub fn unused_colors(&self) -> Vec<u8> {
+        let mut used = vec![false; self.nb_colors.into()];
+        for &color in self.tiles.iter().flat_map(ProtoPalette::colors) {
+            if let Some(used) = used.get_mut(usize::from(color)) {
+                *used = true;
+            }
+        }
+        (0..self.nb_colors)
+            .filter(|&color| !used[usize::from(color)])
+            .collect()
+    }
+
+    /// Which of the optimal palettes can display each tile (the first one that has all of its colours).
+    ///
+    /// Every strategy's tiles all have one, but a spec file's `optimal_palettes` may be missing or wrong,
+    /// so the first tile that fits in none of them is reported.
+    pub fn optimal_assignment(&self) -> Result<Vec<usize>, AssignmentError> {
+        self.tiles
+            .iter()
+            .enumerate()
+            .map(|(tile, colors)| {
+                self.optimal_palettes
+                    .iter()
+                    .position(|palette| colors.colors().iter().all(|color| palette.contains(color)))
+                    .ok_or(AssignmentError::Unassigned { tile })
+            })
+            .collect()
+    }
+
+    /// Makes the tiles repeat `times` times over (so, `times` times more tiles), e.g. for stress-testing packers.
+    ///
+    /// The whole list is repeated, so the order that the strategy relies on is preserved;
+    /// and since no new colours are introduced, the optimal palettes stay the same.
+    pub fn repeat(&mut self, times: usize) {
+        let nb_tiles = self.tiles.len() * times;
+        self.tiles = self.tiles.iter().cycle().take(nb_tiles).cloned().collect();
+    }
+
+    /// Shuffles the tiles around, reproducibly, using this seed.
+    ///
+    /// Most strategies depend on the order that they emit tiles in, so this can make them a lot less effective,
+    /// or not effective at all; this is meant for checking whether they are. The optimal palettes stay the same.
+    pub fn shuffle(&mut self, seed: u64) {
+        let mut rng = SplitMix64(seed);
+        for i in (1..self.tiles.len()).rev() {
+            self.tiles.swap(i, rng.below(i + 1));
+        }
+    }
+
+    /// Puts the tiles in this order, e.g. to check how sensitive a packer is to it, deterministically unlike [`shuffle`][Self::shuffle].
+    /// The optimal palettes stay the same.
+    pub fn reorder(&mut self, order: TileOrder) {
+        match order {
+            TileOrder::Crafted => {}
+            TileOrder::Reverse => self.tiles.reverse(),
+            TileOrder::Sorted => self.tiles.sort_by_cached_key(|tile| {
+                let mut colors = tile.colors().to_vec();
+                colors.sort_unstable();
+                colors
+            }),
+        }
+    }
+
+    /// Puts the tiles in the order given by `order`, which lists each tile's index once: the new `i`th tile is `order[i]`.
+    /// E.g. to reproduce an order observed in the wild; see [`read_tile_order_file`]. The optimal palettes stay the same.
+    ///
+    /// Panics if `order` isn't a permutation of the tiles' indices.
+    pub fn permute(&mut self, order: &[usize]) {
+        assert_eq!(
+            order.len(),
+            self.tiles.len(),
+            "The order must list every tile!"
+        );
+        let mut tiles: Vec<_> = std::mem::take(&mut self.tiles)
+            .into_iter()
+            .map(Some)
+            .collect();
+        self.tiles = order
+            .iter()
+            .map(|&i| tiles[i].take().expect("The order lists a tile twice!"))
+            .collect();
+    }
+
+    /// Regroups the tiles into runs of `period` tiles from one optimal palette, then `period` from the other, and so on,
+    /// keeping their order within each palette; e.g. to probe packers that look a few tiles ahead.
+    /// The first tile's palette goes first, and once either palette runs out of tiles, the rest of the other's follow.
+    ///
+    /// This is meant for attacks made of two disjoint sets of colours whose tiles alternate between them,
+    /// like [`defeat_any_fit`]'s; a period of 1 thus keeps their order.
+    /// Panics if there aren't exactly two optimal palettes, if a tile fits in neither, or if `period` is 0.
+    pub fn interleave_every(&mut self, period: usize) {
+        assert_ne!(period, 0, "There must be at least one tile per run!");
+        assert_eq!(
+            self.optimal_palettes.len(),
+            2,
+            "Only the tiles of two sets can be interleaved!"
+        );
+        let assignment = self
+            .optimal_assignment()
+            .expect("Every tile must fit in one of the optimal palettes!");
+        let first = assignment.first().copied();
+        let (runs_a, runs_b): (Vec<_>, Vec<_>) = std::mem::take(&mut self.tiles)
+            .into_iter()
+            .zip(&assignment)
+            .partition(|&(_, &palette)| Some(palette) == first);
+        let (mut runs_a, mut runs_b) = (runs_a.into_iter(), runs_b.into_iter());
+        loop {
+            let nb_tiles = self.tiles.len();
+            self.tiles
+                .extend(runs_a.by_ref().take(period).map(|(tile, _)| tile));
+            self.tiles
+                .extend(runs_b.by_ref().take(period).map(|(tile, _)| tile));
+            if self.tiles.len() == nb_tiles {
+                break;
+            }
+        }
+    }
+
+    /// How many tiles are pixel-for-pixel identical to an earlier one, once drawn using `fill`.
+    ///
+    /// Most tile-based converters deduplicate tiles before packing their palettes, so they only ever see one of each;
+    /// this weakens strategies that rely on the same proto-palette being fed several times.
+    /// (Some also deduplicate flipped tiles, which this doesn't account for.)
+    pub fn nb_duplicate_tiles(
+        &self,
+        fill: TileFill,
+        tile_width: usize,
+        tile_height: usize,
+    ) -> usize {
+        let mut seen = HashSet::new();
+        self.tiles
+            .iter()
+            .enumerate()
+            .filter(|(i, tile)| {
+                !seen.insert(fill.pixels(*i, tile.colors(), tile_width, tile_height))
+            })
+            .count()
+    }
+
+    /// Reorders the colours of tiles that [`nb_duplicate_tiles`][Self::nb_duplicate_tiles] would count,
+    /// until they no longer look like any earlier tile.
+    ///
+    /// Only the pixels change, not the tiles' sets of colours, so packers that don't deduplicate aren't affected.
+    /// Some tiles can't be told apart no matter what (e.g. solid ones); returns how many duplicates are left.
+    pub fn make_unique(&mut self, fill: TileFill, tile_width: usize, tile_height: usize) -> usize {
+        // Beyond this, looking for an unused arrangement takes too long to be worth it.
+        const MAX_ATTEMPTS: usize = 5040; // 7!
+
+        let mut seen = HashSet::new();
+        let mut nb_left = 0;
+        for (i, tile) in self.tiles.iter_mut().enumerate() {
+            let mut colors = tile.colors().to_vec();
+            let mut attempts = 0;
+            while seen.contains(&fill.pixels(i, &colors, tile_width, tile_height)) {
+                next_permutation(&mut colors);
+                attempts += 1;
+                if attempts == MAX_ATTEMPTS || colors == tile.colors() {
+                    break;
+                }
+            }
+
+            if seen.insert(fill.pixels(i, &colors, tile_width, tile_height)) {
+                *tile = colors.into();
+            } else {
+                nb_left += 1;
+            }
+        }
+        nb_left
+    }
+}
+
+/// The orders that [`Attack::reorder`] can put the tiles in.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum TileOrder {
+    /// The order the strategy emitted them in, which is the one it relies on.
+    #[default]
+    Crafted,
+    /// The opposite of the crafted order.
+    Reverse,
+    /// By their sets of colours, compared lexicographically (so, mostly by lowest colour index).
+    Sorted,
+}
+
+impl TileOrder {
+    pub const ALL: [Self; 3] = [Self::Crafted, Self::Reverse, Self::Sorted];
+
+    /// The name by which the order is selected on the command line.
+    pub fn name(self) -> &'static str {
+        match self {
+            Self::Crafted => "crafted",
+            Self::Reverse => "reverse",
+            Self::Sorted => "sorted",
+        }
+    }
+}
+
+impl FromStr for TileOrder {
+    type Err = UnknownTileOrder;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Self::ALL
+            .into_iter()
+            .find(|order| order.name() == s)
+            .ok_or_else(|| UnknownTileOrder(s.to_owned()))
+    }
+}
+
+/// Returned when parsing a [`TileOrder`] whose name we don't recognise.
+#[derive(Debug)]
+pub struct UnknownTileOrder(String);
+
+impl Display for UnknownTileOrder {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "Unknown tile order \"{}\" (expected one of: ", self.0)?;
+        for (i, order) in TileOrder::ALL.iter().enumerate() {
+            if i != 0 {
+                write!(f, ", ")?;
+            }
+            write!(f, "{}", order.name())?;
+        }
+        write!(f, ")")
+    }
+}
+
+/// Rearranges `slice` into its lexicographically next permutation, wrapping around to the first one after the last.
+fn next_permutation(slice: &mut [u8]) {
+    let Some(pivot) = slice.windows(2).rposition(|pair| pair[0] < pair[1]) else {
+        slice.reverse(); // This was the last permutation.
+        return;
+    };
+    let successor = slice
+        .iter()
+        .rposition(|&elem| elem > slice[pivot])
+        .expect("The element after the pivot is larger");
+    slice.swap(pivot, successor);
+    slice[pivot + 1..].reverse();
+}
+
+/// Checks that every tile has exactly `nb_colors_per_tile` distinct colours, all of them below `nb_colors`.
+///
+/// Strategies rely on this to control what fits where, so it's worth catching if they get refactored wrong.
+fn debug_assert_tiles(tiles: &[ProtoPalette], nb_colors_per_tile: usize, nb_colors: u8) {
+    if cfg!(debug_assertions) {
+        for (i, tile) in tiles.iter().enumerate() {
+            let mut colors = tile.colors().to_vec();
+            colors.sort_unstable();
+            colors.dedup();
+            assert_eq!(
+                colors.len(),
+                nb_colors_per_tile,
+                "Tile #{i} has the wrong number of colours"
+            );
+            assert_eq!(colors.len(), tile.len(), "Tile #{i} has duplicate colours");
+            assert!(
+                colors.iter().all(|&color| color < nb_colors),
+                "Tile #{i} uses colours beyond {nb_colors}"
+            );
+        }
+    }
+}
+
+/// The two palettes that `N` even and `N` odd colour indices form.
+fn even_and_odd_palettes(palette_size: usize) -> Vec<Vec<u8>> {
+    let evens = (0..palette_size).map(|index| (index * 2) as u8).collect();
+    let odds = (0..palette_size)
+        .map(|index| (index * 2 + 1) as u8)
+        .collect();
+    vec![evens, odds]
+}
+
+/// Strategy: (section 3.1.2)
+///
+/// 1. Divide the colours into two palette-sized disjoint sets. (We will use even and odd indices.)
+/// 2. Make sure that each tile only has colours from either set (never both!),
+///    and that these “proto-palettes” are fed to the packing algorithm alternatingly.
+///
+/// The image can be displayed using just the two sets, but the greediness of these algorithms
+/// makes them generate N palettes composed of one “proto-palette” from each set.
+pub fn defeat_any_fit(palette_size: usize) -> Result<Attack, PaletteTooLarge> {
+    Strategy::AnyFit.check_palette_size(palette_size)?;
+    let nb_colors = (palette_size * 2) as u8;
+    let tiles: Vec<_> = any_fit_tiles(palette_size)?.collect();
+    debug_assert_eq!(tiles.len(), nb_tiles(Strategy::AnyFit, palette_size));
+    debug_assert_tiles(&tiles, palette_size / 2, nb_colors);
+
+    Ok(Attack {
+        tiles,
+        nb_colors,
+        optimal_palettes: even_and_odd_palettes(palette_size),
+    })
+}
+
+/// Lazily generates the tiles of [`defeat_any_fit`].
+///
+/// There are (N choose N/2) × 2 of them, which grows *very* quickly; this avoids having to hold them all in memory at once.
+/// (Use [`nb_tiles`] to know how many there will be beforehand.)
+pub fn any_fit_tiles(
+    palette_size: usize,
+) -> Result<impl Iterator<Item = ProtoPalette> + Clone, PaletteTooLarge> {
+    Strategy::AnyFit.check_palette_size(palette_size)?;
+    assert_eq!(
+        palette_size % 2,
+        0,
+        "Palette size must be even for this strategy!"
+    );
+    let nb_colors_per_tile = palette_size / 2;
+
+    Ok(
+        Combinations::new(palette_size, nb_colors_per_tile).flat_map(|combination| {
+            let (evens, odds): (Vec<_>, Vec<_>) = combination
+                .into_iter()
+                .map(|index| {
+                    let even = (index * 2) as u8;
+                    (even, even + 1)
+                })
+                .unzip();
+            [evens.into(), odds.into()]
+        }),
+    )
+}
+
+/// Strategy: (not from the paper; targets packers that colour a "conflict graph" using DSATUR)
+///
+/// Such packers view the tiles as the vertices of a graph, with two tiles being adjacent if they use more than N colours together
+/// (so they can't go in the same palette); each colour of the graph then becomes a palette.
+/// DSATUR packs the tile adjacent to the most distinct palettes first, then the one with the most unpacked neighbours.
+///
+/// 0. Let `N = palette_size`, and let X (even indices) and Y (odd indices) be two disjoint sets of N colours.
+/// 1. Emit a tile using only the last colour of Y, then one using only the last colour of X.
+///    Neither has more than N colours along with any other tile, so they have a degree of 0.
+/// 2. Emit a tile using the first half of Y, then one using the second half; each has a degree of 1.
+/// 3. Emit a tile using all of X but its last colour, which conflicts with both tiles of step 2 (as N/2 > 1);
+///    with a degree of 2, it gets packed first, despite being emitted last, and opens palette #1, which has 1 free slot.
+/// 4. The tiles of step 2 are now adjacent to palette #1, so they get packed next; neither fits in palette #1,
+///    so the first one opens palette #2, and the second one fills it up.
+/// 5. The tiles of step 1 are still not adjacent to any palette, so they get packed in order.
+///    Y's lone colour fits in palette #1, and fills it up; X's lone colour then fits in neither, so a third palette gets opened.
+///
+/// The image can be displayed using just X and Y, but DSATUR only checks compatibility *pairwise*,
+/// so nothing warns it against filling up palette #1 with a colour that doesn't belong there.
+pub fn defeat_dsatur(palette_size: usize) -> Result<Attack, PaletteTooLarge> {
+    Strategy::Dsatur.check_palette_size(palette_size)?;
+    assert_eq!(
+        palette_size % 2,
+        0,
+        "Palette size must be even for this strategy!"
+    );
+    let nb_colors = (palette_size * 2) as u8;
+    let x = |index: usize| (index * 2) as u8;
+    let y = |index: usize| (index * 2 + 1) as u8;
+
+    Ok(Attack {
+        tiles: vec![
+            vec![y(palette_size - 1)].into(),
+            vec![x(palette_size - 1)].into(),
+            (0..palette_size / 2).map(y).collect(),
+            (palette_size / 2..palette_size).map(y).collect(),
+            (0..palette_size - 1).map(x).collect(),
+        ],
+        nb_colors,
+        optimal_palettes: even_and_odd_palettes(palette_size),
+    })
+}
+
+/// Strategy: (section 3.2.2)
+///
+/// 0. Let `N = palette_size`, for conciseness, and `A = 0..N` the input alphabet.
+/// 1. Construct N tiles of (N-1) colours each. (= the (N-1)-combinations of A).
+/// 2. For each (non-overlapping) pair of those tiles, take their intersection (should have size N-2), add two “locking” colours (N and N+1), and do the same as step 1.
+/// 3. Make sure each tile uses one proto-palette from step 1, then one from step 2 (never using both A and B), etc.
+/// 4. If N > 2, emit the remaining tiles that take
+///
+/// The image can be displayed using one palette containing all of A, and one palette containing each of the generated `intersection`s with N and N+1 added.
+pub fn defeat_best_fusion(palette_size: usize) -> Result<Attack, PaletteTooLarge> {
+    Strategy::BestFusion.check_palette_size(palette_size)?;
+    let nb_colors = (palette_size + 2) as u8;
+    let tiles: Vec<_> = best_fusion_tiles(palette_size)?.collect();
+    debug_assert_eq!(tiles.len(), nb_tiles(Strategy::BestFusion, palette_size));
+    debug_assert_tiles(&tiles, palette_size - 1, nb_colors);
+
+    let t0 = combination::combine::index(palette_size, palette_size - 1);
+    let (a, b) = (nb_colors - 2, nb_colors - 1);
+    let mut optimal_palettes = Vec::with_capacity(1 + palette_size / 2);
+    optimal_palettes.push((0..a).collect());
+    optimal_palettes.extend((0..palette_size / 2).map(|i| {
+        let mut locked = best_fusion_intersection(&t0, i);
+        locked.push(a);
+        locked.push(b);
+        locked
+    }));
+
+    // One palette for A, and one per intersection.
+    debug_assert_eq!(optimal_palettes.len(), 1 + palette_size / 2);
+
+    Ok(Attack {
+        tiles,
+        nb_colors,
+        optimal_palettes,
+    })
+}
+
+/// Same as [`defeat_best_fusion`], but with the two "locking" colours at `lock_indices` instead of the last two indices.
+///
+/// The other colours keep their relative order, filling up the remaining indices; e.g. with `[0, 1]`,
+/// the locking colours come first, and the input alphabet is shifted up by 2.
+/// The tiles thus only differ in which indices they use, which tests whether a packer is sensitive to colours' positions.
+///
+/// ```
+/// use no_packsaran::{defeat_best_fusion, defeat_best_fusion_locked_at};
+///
+/// let trailing = defeat_best_fusion(4)?;
+/// assert_eq!(defeat_best_fusion_locked_at(4, [4, 5])?, trailing);
+/// let leading = defeat_best_fusion_locked_at(4, [0, 1])?;
+/// assert_eq!(leading.tiles.len(), trailing.tiles.len());
+/// assert_eq!(leading.optimal_palettes[0], [2, 3, 4, 5]);
+/// # Ok::<(), no_packsaran::LockIndicesError>(())
+/// ```
+pub fn defeat_best_fusion_locked_at(
+    palette_size: usize,
+    lock_indices: [u8; 2],
+) -> Result<Attack, LockIndicesError> {
+    let mut attack = defeat_best_fusion(palette_size)?;
+    let nb_colors = attack.nb_colors;
+    if let Some(&index) = lock_indices.iter().find(|&&index| index >= nb_colors) {
+        return Err(LockIndicesError::OutOfRange { index, nb_colors });
+    }
+    if lock_indices[0] == lock_indices[1] {
+        return Err(LockIndicesError::Same(lock_indices[0]));
+    }
+
+    let (a, b) = (nb_colors - 2, nb_colors - 1);
+    let mut others = (0..nb_colors).filter(|index| !lock_indices.contains(index));
+    // `mapping[old] == new`; the colours other than `a` and `b` are `0..a`, in order.
+    let mapping: Vec<u8> = (0..nb_colors)
+        .map(|color| match color {
+            _ if color == a => lock_indices[0],
+            _ if color == b => lock_indices[1],
+            _ => others.next().unwrap(), // There are as many other indices left as other colours.
+        })
+        .collect();
+    let remap = |colors: &[u8]| -> Vec<u8> {
+        colors
+            .iter()
+            .map(|&color| mapping[usize::from(color)])
+            .collect()
+    };
+    for tile in &mut attack.tiles {
+        *tile = remap(tile.colors()).into();
+    }
+    for palette in &mut attack.optimal_palettes {
+        *palette = remap(palette);
+    }
+    Ok(attack)
+}
+
+/// Returned by [`defeat_best_fusion_locked_at`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LockIndicesError {
+    /// The strategy doesn't support the palette size.
+    PaletteTooLarge(PaletteTooLarge),
+    /// That index isn't one of the tiles' colours, of which there are `nb_colors`.
+    OutOfRange { index: u8, nb_colors: u8 },
+    /// Both locking colours were asked to be at that index.
+    Same(u8),
+}
+
+impl From<PaletteTooLarge> for LockIndicesError {
+    fn from(err: PaletteTooLarge) -> Self {
+        Self::PaletteTooLarge(err)
+    }
+}
+
+impl Display for LockIndicesError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::PaletteTooLarge(err) => err.fmt(f),
+            Self::OutOfRange { index, nb_colors } => write!(
+                f,
+                "Lock index {index} is out of range, as the tiles only use {nb_colors} colours"
+            ),
+            Self::Same(index) => write!(f, "Both locking colours can't be at index {index}"),
+        }
+    }
+}
+
+impl std::error::Error for LockIndicesError {}
+
+/// Lazily generates the tiles of [`defeat_best_fusion`].
+///
+/// Only one "group" of tiles (those related to a single `intersection`) is held in memory at a time.
+pub fn best_fusion_tiles(
+    palette_size: usize,
+) -> Result<impl Iterator<Item = ProtoPalette> + Clone, PaletteTooLarge> {
+    Strategy::BestFusion.check_palette_size(palette_size)?;
+    assert_eq!(
+        palette_size % 2,
+        0,
+        "Palette size must be even for this strategy!"
+    );
+    let nb_colors = (palette_size + 2) as u8;
+
+    let t0 = combination::combine::index(palette_size, palette_size - 1); // Note that `t0.len() == palette_size`.
+    debug_assert_eq!(t0.len(), palette_size); // Guaranteed by maths.
+    let a = nb_colors - 2;
+    let b = nb_colors - 1;
+
+    Ok((0..palette_size / 2).flat_map(move |i| {
+        let first = &t0[i * 2];
+        let second = &t0[i * 2 + 1];
+        let intersection = best_fusion_intersection(&t0, i);
+
+        fn u8ify(slice: &[usize]) -> ProtoPalette {
+            slice.iter().map(|&idx| idx as u8).collect()
+        }
+        let lock = |lock_color| {
+            let mut locked = intersection.clone();
+            locked.push(lock_color);
+            ProtoPalette::from(locked)
+        };
+        let mut group = Vec::with_capacity(4 + palette_size - 2);
+        // These two will go into one palette, and fill it up.
+        group.push(u8ify(first));
+        group.push(lock(a));
+        // These two will go into another one, and fill it up.
+        group.push(u8ify(second));
+        group.push(lock(b));
+
+        // Normally this would be done in a separate loop after this one complete, but honestly we can do both at the same time, and it saves a bit of computation.
+        let rest = combination::combine::from_vec_at(&intersection, palette_size - 3); // We will add 2 extra to end up at `palette_size - 1`.
+        debug_assert_eq!(rest.len(), palette_size - 2); // Guaranteed by maths.
+
+        // This entire `extend` will only generate a single extra palette.
+        group.extend(rest.into_iter().map(|mut subpal| {
+            subpal.push(a);
+            subpal.push(b);
+            ProtoPalette::from(subpal)
+        }));
+        group
+    }))
+}
+
+/// The intersection of the `i`th (non-overlapping) pair of tiles from step 1 of [`defeat_best_fusion`].
+fn best_fusion_intersection(t0: &[Vec<usize>], i: usize) -> Vec<u8> {
+    let first = &t0[i * 2];
+    let second = &t0[i * 2 + 1];
+
+    let intersection: Vec<_> = first
+        .iter()
+        .filter_map(|t| second.contains(t).then_some(*t as u8))
+        .collect();
+    debug_assert_eq!(intersection.len(), first.len() - 1); // We will add 1 extra to end up at `palette_size - 1`.
+    intersection
+}
+
+/// Strategy: (not from the paper, but in the same spirit as section 3.1.2)
+///
+/// 0. Let `N = palette_size`, and let X (even indices) and Y (odd indices) be two disjoint sets of N colours.
+/// 1. Emit a tile using the first half of X; it opens palette #1, which still has N/2 free slots.
+/// 2. Emit a tile using all of Y but one colour; it cannot fit in palette #1, so it opens palette #2, which has 1 free slot.
+/// 3. Emit a tile using only the last colour of Y. It fits in both palettes, and first-fit commits it to palette #1,
+///    since that's the first one it tries.
+/// 4. Emit a tile using the second half of X. Palette #1 now only has N/2 - 1 free slots, and palette #2 is full,
+///    so a third palette gets opened.
+///
+/// The image can be displayed using just X and Y, but first-fit never gets to undo step 3.
+/// (A best-fit packer would have put the lone colour in the fuller palette #2 instead.)
+pub fn defeat_first_fit(palette_size: usize) -> Result<Attack, PaletteTooLarge> {
+    Strategy::FirstFit.check_palette_size(palette_size)?;
+    assert_eq!(
+        palette_size % 2,
+        0,
+        "Palette size must be even for this strategy!"
+    );
+    let nb_colors = (palette_size * 2) as u8;
+    let x = |index: usize| (index * 2) as u8;
+    let y = |index: usize| (index * 2 + 1) as u8;
+
+    Ok(Attack {
+        tiles: vec![
+            (0..palette_size / 2).map(x).collect(),
+            (0..palette_size - 1).map(y).collect(),
+            vec![y(palette_size - 1)].into(),
+            (palette_size / 2..palette_size).map(x).collect(),
+        ],
+        nb_colors,
+        optimal_palettes: even_and_odd_palettes(palette_size),
+    })
+}
+
+/// Strategy: (mirror image of [`defeat_first_fit`]'s)
+///
+/// 0. Let `N = palette_size`, and let X (even indices) and Y (odd indices) be two disjoint sets of N colours.
+/// 1. Emit a tile using all of X but one colour; it opens palette #1, which has 1 free slot.
+/// 2. Emit a tile using the first half of Y; it cannot fit in palette #1, so it opens palette #2, which has N/2 free slots.
+/// 3. Emit a tile using only the last colour of X. It fits in both palettes, and worst-fit commits it to palette #2,
+///    since that's the one with the most room.
+/// 4. Emit a tile using the second half of Y. Palette #1 only has 1 free slot, and palette #2 now only has N/2 - 1,
+///    so a third palette gets opened.
+///
+/// The image can be displayed using just X and Y, but worst-fit's eagerness to spread colours out
+/// is exactly what keeps it from consolidating them into the palette they belong to.
+pub fn defeat_worst_fit(palette_size: usize) -> Result<Attack, PaletteTooLarge> {
+    Strategy::WorstFit.check_palette_size(palette_size)?;
+    assert_eq!(
+        palette_size % 2,
+        0,
+        "Palette size must be even for this strategy!"
+    );
+    let nb_colors = (palette_size * 2) as u8;
+    let x = |index: usize| (index * 2) as u8;
+    let y = |index: usize| (index * 2 + 1) as u8;
+
+    Ok(Attack {
+        tiles: vec![
+            (0..palette_size - 1).map(x).collect(),
+            (0..palette_size / 2).map(y).collect(),
+            vec![x(palette_size - 1)].into(),
+            (palette_size / 2..palette_size).map(y).collect(),
+        ],
+        nb_colors,
+        optimal_palettes: even_and_odd_palettes(palette_size),
+    })
+}
+
+/// Strategy: (not from the paper; targets packers that sort tiles by decreasing colour count, then use best-fit or first-fit)
+///
+/// 0. Let `N = palette_size`, `K = N/2`, and let X (even indices) and Y (odd indices) be two disjoint sets of N colours.
+/// 1. Make every tile use exactly K colours. The sort thus has nothing to reorder,
+///    as long as it's stable. (Most are, e.g. Rust's `sort_by_key` or C++'s `std::stable_sort`.)
+/// 2. Emit a tile using the first half of X, then one using the first half of Y.
+///    They fit together, and best-fit packs them into palette #1, filling it up.
+/// 3. Do the same with the second halves of X and Y, which fill up palette #2.
+/// 4. Emit a tile "bridging" both halves of X: the first K-1 colours of X, plus the first colour of its second half.
+///    Neither full palette has all of them, so a third palette gets opened.
+///
+/// The image can be displayed using just X and Y, but sorting the tiles beforehand doesn't help at all.
+pub fn defeat_best_fit_decreasing(palette_size: usize) -> Result<Attack, PaletteTooLarge> {
+    Strategy::BestFitDecreasing.check_palette_size(palette_size)?;
+    assert_eq!(
+        palette_size % 2,
+        0,
+        "Palette size must be even for this strategy!"
+    );
+    let nb_colors = (palette_size * 2) as u8;
+    let x = |index: usize| (index * 2) as u8;
+    let y = |index: usize| (index * 2 + 1) as u8;
+    let half = palette_size / 2;
+
+    Ok(Attack {
+        tiles: vec![
+            (0..half).map(x).collect(),
+            (0..half).map(y).collect(),
+            (half..palette_size).map(x).collect(),
+            (half..palette_size).map(y).collect(),
+            (0..half - 1).chain([half]).map(x).collect(),
+        ],
+        nb_colors,
+        optimal_palettes: even_and_odd_palettes(palette_size),
+    })
+}
+
+/// Strategy: (not from the paper; targets packers that only ever try adding to the last palette they opened)
+///
+/// 0. Let `N = palette_size`, and let X (even indices) and Y (odd indices) be two disjoint sets of N colours.
+/// 1. Emit a tile using N/2 + 1 colours of X, then one using N/2 colours of Y.
+///    Together, they take up N + 1 colours, so the second one can't fit in the first one's palette,
+///    and has to open a new one.
+/// 2. Repeat N times, "rotating" which colours of X and Y get used, so that each tile just barely fails
+///    to fit with the previous one, and thus opens a new palette.
+///
+/// The image can be displayed using just X and Y, but next-fit generates *2N* palettes.
+/// Next-fit is uniquely vulnerable because it never looks back: any other algorithm would have put every other tile
+/// back into one of the first two palettes, but next-fit has already closed them.
+pub fn defeat_next_fit(palette_size: usize) -> Result<Attack, PaletteTooLarge> {
+    Strategy::NextFit.check_palette_size(palette_size)?;
+    assert_eq!(
+        palette_size % 2,
+        0,
+        "Palette size must be even for this strategy!"
+    );
+    let nb_colors = (palette_size * 2) as u8;
+    let x = |index: usize| (index * 2) as u8;
+    let y = |index: usize| (index * 2 + 1) as u8;
+    let half = palette_size / 2;
+    // When N = 2, the windows over X span all of it, so those tiles are all identical; that doesn't matter to next-fit.
+    let window = |start: usize, len: usize| (start..start + len).map(move |i| i % palette_size);
+
+    Ok(Attack {
+        tiles: (0..palette_size)
+            .flat_map(|start| {
+                [
+                    window(start, (half + 1).min(palette_size)).map(x).collect(),
+                    window(start, half).map(y).collect(),
+                ]
+            })
+            .collect(),
+        nb_colors,
+        optimal_palettes: even_and_odd_palettes(palette_size),
+    })
+}
+
+/// Strategy: (not from the paper; targets superfamiconv's `palette` heuristic, as of v0.9)
+///
+/// superfamiconv drops the proto-palettes that are subsets of others, sorts the rest by decreasing size,
+/// and puts each in the first palette that it fits in.
+/// The tiles generated here are all disjoint, which turns this into plain bin packing (and the sort's stability irrelevant,
+/// as equally-sized tiles are interchangeable); first-fit-decreasing is famously suboptimal at that.
+///
+/// 0. Let `N = palette_size`, and let X and Y be two disjoint sets of N colours.
+/// 1. Split X into tiles of N-6, 4, and 2 colours; and Y into tiles of N-7, 5, and 2 colours.
+/// 2. Sorting puts both big tiles first, which can't fit together. The N-6 one is the first to have room for the 5-colour one,
+///    leaving a single slot; the N-7 one thus gets the 4-colour one, leaving 3 slots.
+/// 3. Only one of the 2-colour tiles can fit in those 3 slots, so the other opens a third palette.
+/// 4. Repeat this with fresh colours, as many times as the SNES has pairs of palettes (4), or as colour indices allow.
+///    The ones of the 2-colour tiles that don't fit can share the same extra palettes, but they still need at least one.
+///
+/// The image can be displayed using each X and Y as a palette, i.e. the 8 palettes of the SNES when `N = 16`,
+/// but superfamiconv needs at least one more.
+pub fn defeat_superfamiconv(palette_size: usize) -> Result<Attack, PaletteTooLarge> {
+    Strategy::Superfamiconv.check_palette_size(palette_size)?;
+    let nb_gadgets = (usize::from(u8::MAX) / (palette_size * 2)).min(4);
+    let nb_colors = (palette_size * 2 * nb_gadgets) as u8;
+
+    let mut tiles = vec![];
+    let mut optimal_palettes = vec![];
+    for gadget in 0..nb_gadgets {
+        let x = gadget * palette_size * 2;
+        let y = x + palette_size;
+        let tile = |start: usize, len: usize| (start..start + len).map(|i| i as u8).collect();
+
+        tiles.extend([
+            tile(x, palette_size - 6),
+            tile(y, palette_size - 7),
+            tile(y + palette_size - 7, 5),
+            tile(x + palette_size - 6, 4),
+            tile(x + palette_size - 2, 2),
+            tile(y + palette_size - 2, 2),
+        ]);
+        optimal_palettes.push((x..y).map(|i| i as u8).collect());
+        optimal_palettes.push((y..y + palette_size).map(|i| i as u8).collect());
+    }
+
+    Ok(Attack {
+        tiles,
+        nb_colors,
+        optimal_palettes,
+    })
+}
+
+/// Strategy: (not from the paper; targets RGBGFX's "overload-and-remove" packer, as of v0.9)
+///
+/// RGBGFX processes the proto-palettes in order of decreasing size, in four phases:
+///
+/// 1. Each proto-palette goes to the palette where its colours are the most shared ("relative size"),
+///    among those it hasn't been kicked out of yet.
+/// 2. If that overloads the palette, the proto-palette most "wasteful" there (lowest size over relative size) is kicked out,
+///    and sent back to the queue; this repeats until the palette fits, *or all of its proto-palettes tie*.
+/// 3. Palettes still overloaded at the end are emptied, and their proto-palettes put in the first palette that fits.
+/// 4. Finally, palettes and proto-palettes that fit into earlier palettes are moved there ("decanting").
+///
+/// The tiles generated here are all disjoint, so each one's relative size is its size, and they all tie in phase 2.
+/// They thus all pile up in the first palette, which phase 3 repacks using first-fit-decreasing.
+///
+/// 0. Let `N = palette_size`, and let X and Y be two disjoint sets of N colours.
+/// 1. Split X into tiles of N-4, 2, and 2 colours; and Y into tiles of N-5, 3, and 2 colours.
+/// 2. Phase 3 puts both big tiles first, which can't fit together. The N-4 one is the first to have room for the 3-colour one,
+///    leaving a single slot; the N-5 one thus gets two of the 2-colour ones, leaving a single slot as well.
+/// 3. The last 2-colour tile opens a third palette; since it doesn't fit in either of the others,
+///    and they don't fit together, phase 4 can't undo this.
+/// 4. Repeat this with fresh colours, as many times as the SNES has pairs of palettes (4), or as colour indices allow.
+///    The ones of the 2-colour tiles that don't fit can share the same extra palettes, but they still need at least one.
+///
+/// The image can be displayed using each X and Y as a palette, but RGBGFX needs at least one more.
+pub fn defeat_overload_and_remove(palette_size: usize) -> Result<Attack, PaletteTooLarge> {
+    Strategy::OverloadAndRemove.check_palette_size(palette_size)?;
+    let nb_gadgets = (usize::from(u8::MAX) / (palette_size * 2)).min(4);
+    let nb_colors = (palette_size * 2 * nb_gadgets) as u8;
+
+    let mut tiles = vec![];
+    let mut optimal_palettes = vec![];
+    for gadget in 0..nb_gadgets {
+        let x = gadget * palette_size * 2;
+        let y = x + palette_size;
+        let tile = |start: usize, len: usize| (start..start + len).map(|i| i as u8).collect();
+
+        tiles.extend([
+            tile(x, palette_size - 4),
+            tile(y, palette_size - 5),
+            tile(y + palette_size - 5, 3),
+            tile(x + palette_size - 4, 2),
+            tile(x + palette_size - 2, 2),
+            tile(y + palette_size - 2, 2),
+        ]);
+        optimal_palettes.push((x..y).map(|i| i as u8).collect());
+        optimal_palettes.push((y..y + palette_size).map(|i| i as u8).collect());
+    }
+
+    Ok(Attack {
+        tiles,
+        nb_colors,
+        optimal_palettes,
+    })
+}
+
+/// Strategy: (not from the paper; targets agglomerative packers, which merge the "most compatible" pair of palettes until none fit)
+///
+/// Such packers start with one palette per distinct proto-palette, then repeatedly merge the two palettes
+/// whose union has the fewest colours (the first such pair, on ties), among the ones that fit in a palette.
+/// Each merge is the cheapest one available, but merging two small palettes can leave them too large to join any other.
+///
+/// 0. Let `N = palette_size`, and `S = N/2 - 1`; since N ≥ 8, two small tiles of S colours fit together, but three don't.
+/// 1. Emit a tile of N-S colours, then a tile of S colours, all distinct from each other: together, they fill up a palette.
+/// 2. Repeat this with fresh colours, as many times (K) as colour indices allow.
+/// 3. The large tiles can't fit together, and merging a small tile into a large one makes a palette of N colours;
+///    but merging two small tiles only makes 2S = N-2, which is smaller. So the packer pairs up all of the small tiles first.
+/// 4. A pair of small tiles can't fit with a large tile, nor with another small tile, so the pairs stay on their own.
+///
+/// The image can be displayed using K palettes, one per large tile and the small one that fills it up,
+/// but the packer needs K/2 more: one per pair of small tiles (a last, unpaired one can still join a large tile).
+pub fn defeat_merge_smallest(palette_size: usize) -> Result<Attack, PaletteTooLarge> {
+    Strategy::MergeSmallest.check_palette_size(palette_size)?;
+    assert_eq!(
+        palette_size % 2,
+        0,
+        "Palette size must be even for this strategy!"
+    );
+    let nb_gadgets = usize::from(u8::MAX) / palette_size;
+    let nb_colors = (palette_size * nb_gadgets) as u8;
+    let small = palette_size / 2 - 1;
+
+    let mut tiles = vec![];
+    let mut optimal_palettes = vec![];
+    for gadget in 0..nb_gadgets {
+        let start = gadget * palette_size;
+        let tile = |start: usize, len: usize| (start..start + len).map(|i| i as u8).collect();
+
+        tiles.push(tile(start, palette_size - small));
+        tiles.push(tile(start + palette_size - small, small));
+        optimal_palettes.push((start..start + palette_size).map(|i| i as u8).collect());
+    }
+    debug_assert_eq!(tiles.len(), nb_tiles(Strategy::MergeSmallest, palette_size));
+
+    Ok(Attack {
+        tiles,
+        nb_colors,
+        optimal_palettes,
+    })
+}
+
+/// Strategy: (not from the paper; targets two-phase packers, which cluster overlapping tiles before bin-packing the clusters)
+///
+/// Such packers work in two phases:
+///
+/// 1. Starting from one cluster per distinct proto-palette, repeatedly merge the two clusters that share the most colours
+///    (the first such pair, on ties), among the ones that fit in a palette together; until no two such clusters share any.
+/// 2. Pack the clusters as if they were proto-palettes, e.g. using first-fit-decreasing.
+///
+/// Merging the most overlapping tiles is locally sensible, as it saves the most palette slots right away;
+/// but the overlap may only be incidental, and the cluster then no longer fits with the tiles that each half belongs with.
+/// Phase 2 can't undo that, as it only ever sees whole clusters.
+///
+/// 0. Let `N = palette_size`, for conciseness, and `S = (N-1)/2`, so that S ≥ 2 but 2 × (N-S) > N.
+///    Let C be a "core" of S colours, and X and Y be two disjoint sets of the N-S other colours.
+/// 1. Emit a tile using C and the first colour of X, then one using C and the first colour of Y.
+///    They share all S colours of C, and S + 2 ≤ N, so they fit together.
+/// 2. Emit a tile using all of X, then one using all of Y. Each shares a single colour with one of the tiles from step 1,
+///    and none with the other tiles.
+/// 3. The tiles from step 1 are thus the most overlapping pair, and get merged first. Their cluster has S + 2 colours,
+///    which together with either tile of step 2 makes N + 1: neither fits anymore.
+/// 4. The tiles of step 2 don't share any colour, so they never get merged either (nor would they fit: 2 × (N-S) > N).
+/// 5. Repeat this with fresh colours, up to 4 times (so that the optimum can still be brute-forced), or as many as colour indices allow.
+///
+/// The image can be displayed using C and X, and C and Y, as palettes (for each repetition),
+/// but the packer ends up with three clusters that no two of fit together, so three palettes.
+pub fn defeat_cluster_then_fit(palette_size: usize) -> Result<Attack, PaletteTooLarge> {
+    Strategy::ClusterThenFit.check_palette_size(palette_size)?;
+    let core = (palette_size - 1) / 2;
+    let nb_gadgets = cluster_then_fit_gadgets(palette_size);
+    let gadget_size = palette_size * 2 - core;
+    let nb_colors = (gadget_size * nb_gadgets) as u8;
+
+    let mut tiles = vec![];
+    let mut optimal_palettes = vec![];
+    for gadget in 0..nb_gadgets {
+        let c = gadget * gadget_size;
+        let x = c + core;
+        let y = c + palette_size;
+        let colors = |range: std::ops::Range<usize>| range.map(|i| i as u8);
+
+        tiles.extend([
+            colors(c..x).chain([x as u8]).collect(),
+            colors(c..x).chain([y as u8]).collect(),
+            colors(x..y).collect(),
+            colors(y..c + gadget_size).collect(),
+        ]);
+        optimal_palettes.push(colors(c..y).collect());
+        optimal_palettes.push(colors(c..x).chain(colors(y..c + gadget_size)).collect());
+    }
+    debug_assert_eq!(
+        tiles.len(),
+        nb_tiles(Strategy::ClusterThenFit, palette_size)
+    );
+
+    Ok(Attack {
+        tiles,
+        nb_colors,
+        optimal_palettes,
+    })
+}
+
+/// How many times [`defeat_cluster_then_fit`] repeats its gadget, each of which uses `2N - (N-1)/2` colours.
+fn cluster_then_fit_gadgets(palette_size: usize) -> usize {
+    let gadget_size = palette_size * 2 - palette_size.saturating_sub(1) / 2;
+    (usize::from(u8::MAX) / gadget_size).min(4)
+}
+
+/// Strategy: (not from the paper; targets first-fit packers for Game Boy sprites, a.k.a. OBJ)
+///
+/// Unlike background tiles, sprites' colour 0 is always transparent: every palette reserves that entry,
+/// and sprites use it for the pixels that should show what's behind them. The DMG also only has 2 sprite palettes.
+/// Colour 0 is thus in every tile here, but never as a "real" colour; this works whether the packer ignores it
+/// (fitting the rest into N-1 slots) or counts it like any other (it's then in every palette, taking up one slot each).
+///
+/// 0. Let `N = palette_size`, and let X and Y be two disjoint sets of the N-1 colours other than 0.
+/// 1. Emit a tile using the first colour of X, then one using all of Y but its last colour.
+///    They fit together, and fill up palette #1.
+/// 2. Emit a tile using the rest of X; it opens palette #2, which has 1 free slot.
+/// 3. Emit a tile using the last colour of Y. Palette #1 is full, so it goes to palette #2, filling it up too.
+/// 4. Emit a tile using the first two colours of X. Neither palette has both, and both are full,
+///    so a third palette gets opened.
+///
+/// The image can be displayed using X and Y (each with colour 0), i.e. the DMG's 2 sprite palettes,
+/// but first-fit needs a third one. (Colour 0 is only transparent if the image says so, e.g. with `--alpha-index 0`;
+/// and when [combined][defeat_combined] with other strategies, it's only still colour 0 if this one comes first.)
+pub fn defeat_obj(palette_size: usize) -> Result<Attack, PaletteTooLarge> {
+    Strategy::Obj.check_palette_size(palette_size)?;
+    let nb_usable = palette_size - 1;
+    let nb_colors = (nb_usable * 2 + 1) as u8;
+    let x = |index: usize| (1 + index) as u8;
+    let y = |index: usize| (1 + nb_usable + index) as u8;
+    // Every tile has transparent pixels, and thus colour 0.
+    let tile = |colors: Vec<u8>| ProtoPalette::new([0].into_iter().chain(colors).collect());
+
+    let tiles = vec![
+        tile(vec![x(0)]),
+        tile((0..nb_usable - 1).map(y).collect()),
+        tile((1..nb_usable).map(x).collect()),
+        tile(vec![y(nb_usable - 1)]),
+        tile(vec![x(0), x(1)]),
+    ];
+    debug_assert_eq!(tiles.len(), nb_tiles(Strategy::Obj, palette_size));
+
+    Ok(Attack {
+        tiles,
+        nb_colors,
+        optimal_palettes: vec![
+            [0].into_iter().chain((0..nb_usable).map(x)).collect(),
+            [0].into_iter().chain((0..nb_usable).map(y)).collect(),
+        ],
+    })
+}
+
+/// Strategy: (not from the paper; targets packers that sort tiles by decreasing colour count,
+/// breaking ties in favour of the tile whose lowest colour index is the smallest, and then use first-fit)
+///
+/// Unlike a merely stable sort (see [`defeat_best_fit_decreasing`]), this tie-break ignores the order the tiles come in;
+/// but it's just as deterministic, so the colours' indices can be picked to choose the order instead.
+///
+/// 0. Let `N = palette_size`, `K = N/2`, and let X and Y be two disjoint sets of N colours,
+///    each split into two halves of K colours: X₁ and X₂, and Y₁ and Y₂.
+/// 1. Make every tile use exactly K colours, so that only the tie-break orders them.
+/// 2. Give the halves' lowest colours the indices 0 (X₁), 1 (Y₁), 2 (X₂), and 3 (Y₂); the rest come after.
+/// 3. The packer thus handles X₁ first, opening palette #1, and then Y₁, which fits together with it, filling it up.
+///    Then, the same happens with X₂ and Y₂, in palette #2.
+/// 4. Emit a tile "bridging" both halves of X: all of X₁ but 0, plus one colour of X₂. Its lowest colour is 4,
+///    so it comes last, and neither full palette has all of its colours, so a third palette gets opened.
+/// 5. Emit the tiles in the reverse of the above order, so that it's indeed the tie-break that reorders them.
+///
+/// The image can be displayed using just X and Y, but first-fit-decreasing needs three palettes.
+pub fn defeat_ffd_index_tiebreak(palette_size: usize) -> Result<Attack, PaletteTooLarge> {
+    Strategy::FfdIndexTiebreak.check_palette_size(palette_size)?;
+    assert_eq!(
+        palette_size % 2,
+        0,
+        "Palette size must be even for this strategy!"
+    );
+    let nb_colors = (palette_size * 2) as u8;
+    let half = palette_size / 2;
+    // Each half's lowest colour is `first`, and the others (which all come after 3) start at `rest`.
+    let half_tile = |first: u8, rest: usize| -> Vec<u8> {
+        [first]
+            .into_iter()
+            .chain((rest..rest + half - 1).map(|color| color as u8))
+            .collect()
+    };
+    let x1 = half_tile(0, 4);
+    let x2 = half_tile(2, 4 + (half - 1));
+    let y1 = half_tile(1, 4 + (half - 1) * 2);
+    let y2 = half_tile(3, 4 + (half - 1) * 3);
+    let bridge: Vec<u8> = x1[1..].iter().copied().chain([x2[1]]).collect();
+
+    let mut x: Vec<u8> = x1.iter().chain(&x2).copied().collect();
+    x.sort_unstable();
+    let mut y: Vec<u8> = y1.iter().chain(&y2).copied().collect();
+    y.sort_unstable();
+    let tiles: Vec<ProtoPalette> = [bridge, y2, x2, y1, x1]
+        .into_iter()
+        .map(ProtoPalette::new)
+        .collect();
+    debug_assert_eq!(
+        tiles.len(),
+        nb_tiles(Strategy::FfdIndexTiebreak, palette_size)
+    );
+    debug_assert_tiles(&tiles, half, nb_colors);
+
+    Ok(Attack {
+        tiles,
+        nb_colors,
+        optimal_palettes: vec![x, y],
+    })
+}
+
+/// Strategy: (not from the paper; tests several weaknesses at once)
+///
+/// 0. Let `N = palette_size`.
+/// 1. Run each of `strategies` with N, in order.
+/// 2. Shift each attack's colour indices up by the total number of colours of the ones before it
+///    (so the first one's stay the same, the second one's start right after the first one's last, etc.).
+///    No two attacks thus share any colours, and each attack's optimal palettes stay valid.
+/// 3. Emit all tiles, one attack's after the other's.
+///
+/// This can fail if a strategy doesn't support N, or if all of their colours don't fit in a byte;
+/// and like the strategies' own functions, this panics if N is too small for one of them.
+pub fn defeat_combined(
+    strategies: &[Strategy],
+    palette_size: usize,
+) -> Result<Attack, CombineError> {
+    let mut combined = Attack {
+        tiles: vec![],
+        nb_colors: 0,
+        optimal_palettes: vec![],
+    };
+    let mut nb_colors = 0usize;
+    for &strategy in strategies {
+        let attack = defeat_single(strategy, palette_size)?;
+
+        // Checked before shifting, so that the `u8` additions below don't overflow.
+        let offset = nb_colors;
+        nb_colors += usize::from(attack.nb_colors);
+        if nb_colors > usize::from(u8::MAX) {
+            return Err(CombineError::TooManyColors(nb_colors));
+        }
+        let shift = |color: u8| color + offset as u8;
+        combined.tiles.extend(
+            attack
+                .tiles
+                .iter()
+                .map(|tile| tile.colors().iter().copied().map(shift).collect()),
+        );
+        combined.optimal_palettes.extend(
+            attack
+                .optimal_palettes
+                .iter()
+                .map(|palette| palette.iter().copied().map(shift).collect()),
+        );
+    }
+    combined.nb_colors = nb_colors as u8;
+    Ok(combined)
+}
+
+/// Runs the `defeat_*` function corresponding to `strategy`.
+///
+/// [`Strategy::Combined`] needs to know what to combine, so it's rejected with [`CombineError::Nested`];
+/// use [`defeat_combined`] for it instead.
+pub fn defeat_single(strategy: Strategy, palette_size: usize) -> Result<Attack, CombineError> {
+    Ok(match strategy {
+        Strategy::AnyFit => defeat_any_fit(palette_size)?,
+        Strategy::BestFusion => defeat_best_fusion(palette_size)?,
+        Strategy::FirstFit => defeat_first_fit(palette_size)?,
+        Strategy::WorstFit => defeat_worst_fit(palette_size)?,
+        Strategy::BestFitDecreasing => defeat_best_fit_decreasing(palette_size)?,
+        Strategy::NextFit => defeat_next_fit(palette_size)?,
+        Strategy::Dsatur => defeat_dsatur(palette_size)?,
+        Strategy::Superfamiconv => defeat_superfamiconv(palette_size)?,
+        Strategy::OverloadAndRemove => defeat_overload_and_remove(palette_size)?,
+        Strategy::MergeSmallest => defeat_merge_smallest(palette_size)?,
+        Strategy::Obj => defeat_obj(palette_size)?,
+        Strategy::FfdIndexTiebreak => defeat_ffd_index_tiebreak(palette_size)?,
+        Strategy::ClusterThenFit => defeat_cluster_then_fit(palette_size)?,
+        Strategy::Combined => return Err(CombineError::Nested),
+    })
+}
+
+/// Returned by [`defeat_combined`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CombineError {
+    /// One of the strategies doesn't support the palette size.
+    PaletteTooLarge(PaletteTooLarge),
+    /// The strategies need this many colours together, more than fit in a byte.
+    TooManyColors(usize),
+    /// [`Strategy::Combined`] was asked to combine itself.
+    Nested,
+}
+
+impl From<PaletteTooLarge> for CombineError {
+    fn from(err: PaletteTooLarge) -> Self {
+        Self::PaletteTooLarge(err)
+    }
+}
+
+impl Display for CombineError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::PaletteTooLarge(err) => err.fmt(f),
+            Self::TooManyColors(nb_colors) => write!(
+                f,
+                "The combined strategies need {nb_colors} colours together, but at most 255 are supported"
+            ),
+            Self::Nested => write!(f, "The combined strategy can't be combined with itself"),
+        }
+    }
+}
+
+impl std::error::Error for CombineError {}
+
+/// The colours of each tile that `strategy` generates, one tile at a time.
+///
+/// The tiles of [`Strategy::AnyFit`] and [`Strategy::BestFusion`] are generated lazily
+/// (see [`any_fit_tiles`] and [`best_fusion_tiles`]), which are the only ones numerous enough to matter;
+/// [`Strategy::Combined`] chains its [default components][Strategy::COMBINED_DEFAULT]' like [`defeat_combined`] does.
+/// So, unlike going through [`Attack::tiles`], this never holds all of the tiles in memory at once.
+///
+/// This fails like [`defeat_combined`]; and like the strategies' own functions, this panics if N is too small.
+///
+/// ```
+/// use no_packsaran::{nb_tiles, proto_palettes, Strategy};
+///
+/// let strategies = [Strategy::AnyFit, Strategy::BestFusion, Strategy::FirstFit];
+/// let mut nb_tiles_total = 0;
+/// for strategy in strategies {
+///     nb_tiles_total += proto_palettes(strategy, 8)?.count();
+/// }
+/// let expected: usize = strategies.map(|strategy| nb_tiles(strategy, 8)).iter().sum();
+/// assert_eq!(nb_tiles_total, expected);
+/// assert_eq!(proto_palettes(Strategy::Combined, 8)?.count(), nb_tiles(Strategy::Combined, 8));
+/// # Ok::<(), no_packsaran::CombineError>(())
+/// ```
+pub fn proto_palettes(
+    strategy: Strategy,
+    palette_size: usize,
+) -> Result<impl Iterator<Item = Vec<u8>>, CombineError> {
+    let components = match strategy {
+        Strategy::Combined => &Strategy::COMBINED_DEFAULT[..],
+        _ => std::slice::from_ref(&strategy),
+    };
+    let mut tiles: Box<dyn Iterator<Item = Vec<u8>>> = Box::new(std::iter::empty());
+    let mut nb_colors = 0usize;
+    for &component in components {
+        let (component_tiles, nb_component_colors): (Box<dyn Iterator<Item = ProtoPalette>>, u8) =
+            match component {
+                Strategy::AnyFit => (
+                    Box::new(any_fit_tiles(palette_size)?),
+                    (palette_size * 2) as u8,
+                ),
+                Strategy::BestFusion => (
+                    Box::new(best_fusion_tiles(palette_size)?),
+                    (palette_size + 2) as u8,
+                ),
+                _ => {
+                    let attack = defeat_single(component, palette_size)?;
+                    (Box::new(attack.tiles.into_iter()), attack.nb_colors)
+                }
+            };
+
+        // Same shifting as `defeat_combined`, which is a no-op for a single strategy.
+        let offset = nb_colors;
+        nb_colors += usize::from(nb_component_colors);
+        if nb_colors > usize::from(u8::MAX) {
+            return Err(CombineError::TooManyColors(nb_colors));
+        }
+        tiles = Box::new(tiles.chain(component_tiles.map(move |tile| {
+            let mut colors = tile.into_colors();
+            for color in &mut colors {
+                *color += offset as u8;
+            }
+            colors
+        })));
+    }
+    Ok(tiles)
+}
+
+/// Which of the optimal palettes each tile that `strategy` generates belongs to, in the order they are emitted.
+///
+/// This is [`Attack::optimal_assignment`] of the strategy's tiles, every one of which belongs to one of its optimal palettes;
+/// e.g. [`Strategy::AnyFit`]'s alternate between the even and odd colours.
+/// [`Strategy::Combined`] combines its [default components][Strategy::COMBINED_DEFAULT], and this fails like [`defeat_combined`].
+/// This returns a `Result` nonetheless, as the strategy may not support the palette size;
+/// and a tile that fits in none of the palettes (which would be a bug in the strategy) is reported too, instead of panicking.
+///
+/// ```
+/// use no_packsaran::{optimal_assignment, Strategy};
+///
+/// assert_eq!(optimal_assignment(Strategy::AnyFit, 4)?, [0, 1].repeat(6));
+/// assert_eq!(optimal_assignment(Strategy::FirstFit, 4)?, [0, 1, 1, 0]);
+/// # Ok::<(), no_packsaran::AssignmentError>(())
+/// ```
+pub fn optimal_assignment(
+    strategy: Strategy,
+    palette_size: usize,
+) -> Result<Vec<usize>, AssignmentError> {
+    let attack = match strategy {
+        Strategy::Combined => defeat_combined(&Strategy::COMBINED_DEFAULT, palette_size)?,
+        _ => defeat_single(strategy, palette_size)?,
+    };
+    attack.optimal_assignment()
+}
+
+/// Returned by [`optimal_assignment`] and [`Attack::optimal_assignment`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AssignmentError {
+    /// The tiles couldn't be generated in the first place.
+    Defeat(CombineError),
+    /// The tile of that (0-based) index fits in none of the optimal palettes.
+    Unassigned { tile: usize },
+}
+
+impl From<CombineError> for AssignmentError {
+    fn from(err: CombineError) -> Self {
+        Self::Defeat(err)
+    }
+}
+
+impl Display for AssignmentError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Defeat(err) => err.fmt(f),
+            Self::Unassigned { tile } => {
+                write!(f, "Tile #{tile} isn't in any of the optimal palettes")
+            }
+        }
+    }
+}
+
+impl std::error::Error for AssignmentError {}
+
+/// Strategy: (not from the paper; targets hardware whose palettes don't all have the same size)
+///
+/// This targets first-fit over a fixed list of palettes, whose capacities are `sizes`, in that order:
+/// each tile goes in the first palette that has room for it, and any tile that fits in none of them needs an extra palette.
+/// Unlike with uniform sizes, a tile that's small enough for any palette gets committed to the first one,
+/// even if it belongs in another; and the room it takes up may be the only place a larger tile would have fit in.
+///
+/// 0. Let C be the size of the last palette, and #i be the last palette whose size S differs from C.
+///    Every palette gets its own set of colours, as many as it can hold.
+/// 1. Emit one tile per palette before #i, using all of that palette's colours. Each fills up its palette exactly.
+/// 2. If S is larger than C:
+///    1. Emit a tile using all of palette #i+1's colours. All of the palettes before #i are full, so it goes in #i.
+///    2. Emit a tile using all of #i's colours. #i doesn't have room for it anymore, and the palettes after it are too small.
+///    3. Emit the tiles for the palettes after #i+1, like in step 1.
+/// 3. If S is smaller than C (which requires S ≥ 4):
+///    1. Emit the tiles for the palettes between #i and the last one, like in step 1.
+///       They can't go in #i, which is too small for them.
+///    2. Emit a tile using 2 of the last palette's colours. #i is the first one with room, so it goes there.
+///    3. Emit a tile using S-1 of #i's colours. #i only has S-2 free slots, so it goes in the last palette.
+///    4. Emit a tile using the rest of the last palette's colours (C-2). #i has fewer than C-2 free slots,
+///       and the last palette only C-S+1, which is fewer as well.
+///    5. Emit a tile using the last of #i's colours.
+///
+/// The image can be displayed using each palette's set of colours, but first-fit needs one more palette than there are.
+/// This fails if all palettes have the same size, if one of them can't hold any colours, or if they can't all have their own colours.
+pub fn defeat_mixed(sizes: &[usize]) -> Result<Attack, MixedSizesError> {
+    if sizes.contains(&0) {
+        return Err(MixedSizesError::EmptyPalette);
+    }
+    let &[.., last] = sizes else {
+        return Err(MixedSizesError::Uniform);
+    };
+    let Some(i) = sizes.iter().rposition(|&size| size != last) else {
+        return Err(MixedSizesError::Uniform);
+    };
+    if sizes[i] < last && sizes[i] < 4 {
+        return Err(MixedSizesError::TooSmall(sizes[i]));
+    }
+    let nb_colors: usize = sizes.iter().sum();
+    if nb_colors > usize::from(u8::MAX) {
+        return Err(MixedSizesError::TooManyColors(nb_colors));
+    }
+
+    let optimal_palettes: Vec<Vec<u8>> = sizes
+        .iter()
+        .scan(0, |start, &size| {
+            let palette = (*start..*start + size).map(|color| color as u8).collect();
+            *start += size;
+            Some(palette)
+        })
+        .collect();
+    let tile = |palette: usize, range: std::ops::Range<usize>| -> ProtoPalette {
+        optimal_palettes[palette][range].iter().copied().collect()
+    };
+    let whole = |palette: usize| tile(palette, 0..sizes[palette]);
+
+    let mut tiles: Vec<ProtoPalette> = (0..i).map(whole).collect();
+    let (small, large) = (sizes[i], last);
+    if small > large {
+        tiles.push(whole(i + 1));
+        tiles.push(whole(i));
+        tiles.extend((i + 2..sizes.len()).map(whole));
+    } else {
+        let j = sizes.len() - 1;
+        tiles.extend((i + 1..j).map(whole));
+        tiles.push(tile(j, 0..2));
+        tiles.push(tile(i, 0..small - 1));
+        tiles.push(tile(j, 2..large));
+        tiles.push(tile(i, small - 1..small));
+    }
+
+    Ok(Attack {
+        tiles,
+        nb_colors: nb_colors as u8,
+        optimal_palettes,
+    })
+}
+
+/// Returned by [`defeat_mixed`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MixedSizesError {
+    /// There are no palettes, or they all have the same size; the other strategies are for those.
+    Uniform,
+    /// One of the palettes has a size of 0.
+    EmptyPalette,
+    /// The last palette whose size differs from the last one's is smaller than it, and only has this many colours (fewer than 4).
+    TooSmall(usize),
+    /// The palettes hold this many colours together, more than fit in a byte.
+    TooManyColors(usize),
+}
+
+impl Display for MixedSizesError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Uniform => write!(f, "The palettes must not all have the same size"),
+            Self::EmptyPalette => write!(f, "Every palette must have room for at least one colour"),
+            Self::TooSmall(size) => write!(
+                f,
+                "The last palette smaller than the final one must have at least 4 colours, not {size}"
+            ),
+            Self::TooManyColors(nb_colors) => write!(
+                f,
+                "The palettes hold {nb_colors} colours together, but at most 255 are supported"
+            ),
+        }
+    }
+}
+
+impl std::error::Error for MixedSizesError {}
+
+/// How many palettes the algorithm targeted by `strategy` gets forced into by the tiles that it generates,
+/// for the same `palette_size` as passed to the corresponding `defeat_*` function.
+///
+/// This is derived from each strategy's construction, so nothing gets generated.
+/// The counts for [`Strategy::AnyFit`] grow so quickly that they saturate at [`usize::MAX`].
+///
+/// ```
+/// use no_packsaran::{worst_case_palettes, Strategy};
+///
+/// assert_eq!(worst_case_palettes(Strategy::AnyFit, 4), 6);
+/// assert_eq!(worst_case_palettes(Strategy::AnyFit, 6), 20);
+/// assert_eq!(worst_case_palettes(Strategy::BestFusion, 4), 6);
+/// assert_eq!(worst_case_palettes(Strategy::BestFusion, 6), 9);
+/// ```
+pub fn worst_case_palettes(strategy: Strategy, palette_size: usize) -> usize {
+    match strategy {
+        // One palette per pair of tiles, i.e. per combination of N/2 colours.
+        Strategy::AnyFit => {
+            let half = palette_size / 2;
+            (0..half)
+                .try_fold(1usize, |acc, i| {
+                    // (N choose i+1) = (N choose i) × (N - i) / (i + 1), which is always exact.
+                    acc.checked_mul(palette_size - i).map(|acc| acc / (i + 1))
+                })
+                .unwrap_or(usize::MAX)
+        }
+        // One palette per tile of step 1, plus one per intersection.
+        Strategy::BestFusion => palette_size + palette_size / 2,
+        Strategy::FirstFit
+        | Strategy::WorstFit
+        | Strategy::BestFitDecreasing
+        | Strategy::Dsatur
+        | Strategy::FfdIndexTiebreak => 3,
+        Strategy::NextFit => palette_size * 2,
+        // Two palettes per gadget, plus a single extra one.
+        Strategy::Superfamiconv | Strategy::OverloadAndRemove => {
+            let nb_gadgets = (usize::from(u8::MAX) / (palette_size * 2)).min(4);
+            nb_gadgets * 2 + 1
+        }
+        // The gadgets' own palettes, plus one per pair of small tiles.
+        Strategy::MergeSmallest => {
+            let nb_gadgets = usize::from(u8::MAX) / palette_size;
+            nb_gadgets + nb_gadgets / 2
+        }
+        Strategy::Obj => 3,
+        // Three clusters per gadget.
+        Strategy::ClusterThenFit => cluster_then_fit_gadgets(palette_size) * 3,
+        // These are for the default ones, whose attacks don't interfere with each other: the algorithms are the same.
+        Strategy::Combined => Strategy::COMBINED_DEFAULT
+            .iter()
+            .map(|&strategy| worst_case_palettes(strategy, palette_size))
+            .fold(0, usize::saturating_add),
+    }
+}
+
+/// How many tiles the `defeat_*` function corresponding to `strategy` generates, for that `palette_size`.
+///
+/// Like [`worst_case_palettes`], this is derived from each strategy's construction, so nothing gets generated;
+/// it's meant for checking that there won't be too many tiles to hold in memory *before* generating them.
+/// The counts for [`Strategy::AnyFit`] saturate at [`usize::MAX`] as well.
+///
+/// ```
+/// use no_packsaran::{nb_tiles, Strategy};
+///
+/// assert_eq!(nb_tiles(Strategy::AnyFit, 4), 12);
+/// assert_eq!(nb_tiles(Strategy::AnyFit, 32), 1_202_160_780);
+/// assert_eq!(nb_tiles(Strategy::FirstFit, 32), 4);
+/// ```
+pub fn nb_tiles(strategy: Strategy, palette_size: usize) -> usize {
+    match strategy {
+        // Two tiles per combination of N/2 colours.
+        Strategy::AnyFit => worst_case_palettes(strategy, palette_size).saturating_mul(2),
+        // N pairs of tiles in step 1, and N/2 × (N-2) in step 2.
+        Strategy::BestFusion => {
+            palette_size * 2 + (palette_size / 2) * palette_size.saturating_sub(2)
+        }
+        Strategy::FirstFit | Strategy::WorstFit => 4,
+        Strategy::BestFitDecreasing | Strategy::Dsatur | Strategy::FfdIndexTiebreak => 5,
+        Strategy::NextFit => palette_size * 2,
+        // Six tiles per gadget.
+        Strategy::Superfamiconv | Strategy::OverloadAndRemove => {
+            let nb_gadgets = (usize::from(u8::MAX) / (palette_size * 2)).min(4);
+            nb_gadgets * 6
+        }
+        Strategy::MergeSmallest => usize::from(u8::MAX) / palette_size * 2,
+        Strategy::Obj => 5,
+        // Four tiles per gadget.
+        Strategy::ClusterThenFit => cluster_then_fit_gadgets(palette_size) * 4,
+        Strategy::Combined => Strategy::COMBINED_DEFAULT
+            .iter()
+            .map(|&strategy| nb_tiles(strategy, palette_size))
+            .fold(0, usize::saturating_add),
+    }
+}
+
+/// All strategies require an even palette size, so odd ones lose their last slot.
+pub fn round_down_to_even(palette_size: usize) -> usize {
+    palette_size & !1
+}
+
+/// The smallest palette size at which `strategy`'s packer can be forced into exactly `nb_palettes` palettes;
+/// see [`defeat_targeting`].
+///
+/// ```
+/// use no_packsaran::{palette_size_for_target, Strategy};
+///
+/// // (6 choose 3) = 20 is the first to reach 7.
+/// assert_eq!(palette_size_for_target(Strategy::AnyFit, 7), Ok(6));
+/// assert_eq!(palette_size_for_target(Strategy::NextFit, 12), Ok(6));
+/// assert!(palette_size_for_target(Strategy::NextFit, 13).is_err());
+/// ```
+pub fn palette_size_for_target(
+    strategy: Strategy,
+    nb_palettes: usize,
+) -> Result<usize, UnreachableTarget> {
+    let unreachable = UnreachableTarget {
+        strategy,
+        nb_palettes,
+    };
+    // Not all strategies support odd palette sizes, so stick to even ones.
+    let mut sizes =
+        (strategy.min_palette_size()..=strategy.max_palette_size()).filter(|size| size % 2 == 0);
+    match strategy {
+        // This can stop at any pair of tiles, so it only needs enough of them.
+        Strategy::AnyFit => {
+            // Fewer palettes than that wouldn't exceed the optimal 2.
+            if nb_palettes < 3 {
+                return Err(unreachable);
+            }
+            sizes.find(|&size| worst_case_palettes(strategy, size) >= nb_palettes)
+        }
+        // The default components' counts add up, which makes finding any given total not worth it.
+        Strategy::Combined => None,
+        _ => sizes.find(|&size| worst_case_palettes(strategy, size) == nb_palettes),
+    }
+    .ok_or(unreachable)
+}
+
+/// Strategy: (not from the paper; scales another strategy to force exactly `nb_palettes` palettes)
+///
+/// Generates `strategy`'s tiles for the smallest palette size N that achieves this (see [`palette_size_for_target`]):
+/// - Most strategies force a number of palettes that only depends on N (see [`worst_case_palettes`]),
+///   so this is whichever N forces exactly `nb_palettes`; if none does, the target is unreachable.
+/// - [`Strategy::AnyFit`]'s tiles come in pairs, each of which fills up a palette of its own, so only the first
+///   `nb_palettes` pairs are kept; N is thus the smallest one with enough of them, i.e. with (N choose N/2) ≥ `nb_palettes`.
+///   The optimal palettes stay the even and the odd colours, so at least 3 palettes are needed to exceed them.
+///
+/// [`Strategy::Combined`] isn't supported, as its components' palettes add up.
+pub fn defeat_targeting(
+    strategy: Strategy,
+    nb_palettes: usize,
+) -> Result<Attack, UnreachableTarget> {
+    let palette_size = palette_size_for_target(strategy, nb_palettes)?;
+    let unsupported = "The palette size was picked among those that the strategy supports";
+    Ok(match strategy {
+        // Any two pairs of tiles differ by at least one colour, so no prefix of them can share a palette either.
+        Strategy::AnyFit => Attack {
+            tiles: any_fit_tiles(palette_size)
+                .expect(unsupported)
+                .take(nb_palettes * 2)
+                .collect(),
+            nb_colors: (palette_size * 2) as u8,
+            optimal_palettes: even_and_odd_palettes(palette_size),
+        },
+        _ => defeat_single(strategy, palette_size).expect(unsupported),
+    })
+}
+
+/// Returned by [`palette_size_for_target`] and [`defeat_targeting`] when no palette size works.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct UnreachableTarget {
+    pub strategy: Strategy,
+    pub nb_palettes: usize,
+}
+
+impl Display for UnreachableTarget {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "The {} strategy can't force exactly {} palettes at any palette size",
+            self.strategy.name(),
+            self.nb_palettes
+        )
+    }
+}
+
+impl std::error::Error for UnreachableTarget {}
+
+/// Tweaks for the rainbow generated by [`build_palette`].
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct PaletteStyle {
+    /// Which colour space the hues are spread across.
+    pub color_space: ColorSpace,
+    /// The saturation of all colours, between 0 and 1 (in LCh, this scales the chroma instead).
+    pub saturation: f32,
+    /// The lightness of the "dark" and "bright" colours, respectively, between 0 and 1.
+    pub lightness: [f32; 2],
+    /// How the dark and bright colours are laid out in the palette.
+    pub order: ColorOrder,
+    /// The hue of the first colour, in degrees; the others' are counted from there, wrapping around.
+    pub hue_offset: f32,
+    /// How many degrees of the colour wheel the hues are spread across, at most 360 (the whole wheel).
+    pub hue_span: f32,
+}
+
+impl Default for PaletteStyle {
+    fn default() -> Self {
+        Self {
+            color_space: ColorSpace::default(),
+            saturation: 1.0,         // Max saturation.
+            lightness: [0.25, 0.75], // Alternate between darker and brighter colours.
+            order: ColorOrder::default(),
+            hue_offset: 0.,
+            hue_span: 360., // The whole wheel.
+        }
+    }
+}
+
+/// How [`build_palette`] arranges the dark and bright colours.
+///
+/// This only affects which colour each index gets, not which indices the tiles use.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum ColorOrder {
+    /// Each dark colour is immediately followed by the bright colour of the same hue.
+    #[default]
+    Interleaved,
+    /// All of the dark colours come first, then all of the bright ones, in the same order of hues.
+    Grouped,
+}
+
+impl ColorOrder {
+    pub const ALL: [Self; 2] = [Self::Interleaved, Self::Grouped];
+
+    /// The name by which the order is selected on the command line.
+    pub fn name(self) -> &'static str {
+        match self {
+            Self::Interleaved => "interleaved",
+            Self::Grouped => "grouped",
+        }
+    }
+}
+
+impl FromStr for ColorOrder {
+    type Err = UnknownColorOrder;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Self::ALL
+            .into_iter()
+            .find(|order| order.name() == s)
+            .ok_or_else(|| UnknownColorOrder(s.to_owned()))
+    }
+}
+
+/// Returned when parsing a [`ColorOrder`] whose name we don't recognise.
+#[derive(Debug)]
+pub struct UnknownColorOrder(String);
+
+impl Display for UnknownColorOrder {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "Unknown colour order \"{}\" (expected one of: ", self.0)?;
+        for (i, order) in ColorOrder::ALL.iter().enumerate() {
+            if i != 0 {
+                write!(f, ", ")?;
+            }
+            write!(f, "{}", order.name())?;
+        }
+        write!(f, ")")
+    }
+}
+
+/// The colour spaces that [`build_palette`] can pick colours in.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum ColorSpace {
+    /// Hue, saturation, and lightness, which is simple but not perceptual:
+    /// e.g. yellow looks much brighter than blue, and greens are hard to tell apart.
+    #[default]
+    Hsl,
+    /// CIE LCh (lightness, chroma, and hue), whose hues look evenly spaced and equally bright.
+    ///
+    /// sRGB can't display very vivid colours at all hues, so a saturation of 1 is a chroma of 40:
+    /// the colours that would still fall outside of sRGB are clamped, mostly dark ones.
+    Lch,
+}
+
+impl ColorSpace {
+    pub const ALL: [Self; 2] = [Self::Hsl, Self::Lch];
+
+    /// The name by which the colour space is selected on the command line.
+    pub fn name(self) -> &'static str {
+        match self {
+            Self::Hsl => "hsl",
+            Self::Lch => "lch",
+        }
+    }
+}
+
+impl FromStr for ColorSpace {
+    type Err = UnknownColorSpace;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Self::ALL
+            .into_iter()
+            .find(|space| space.name() == s)
+            .ok_or_else(|| UnknownColorSpace(s.to_owned()))
+    }
+}
+
+/// Returned when parsing a [`ColorSpace`] whose name we don't recognise.
+#[derive(Debug)]
+pub struct UnknownColorSpace(String);
+
+impl Display for UnknownColorSpace {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "Unknown colour space \"{}\" (expected one of: ", self.0)?;
+        for (i, space) in ColorSpace::ALL.iter().enumerate() {
+            if i != 0 {
+                write!(f, ", ")?;
+            }
+            write!(f, "{}", space.name())?;
+        }
+        write!(f, ")")
+    }
+}
+
+/// Generates `nb_colors` colours that are easy to tell apart.
+///
+/// Each hue is used by a darker and a brighter colour; [`PaletteStyle::order`] says where each of these goes.
+pub fn build_palette(nb_colors: u8, style: PaletteStyle) -> Vec<Rgb32> {
+    let nb_darks = nb_colors.div_ceil(2);
+    (0..nb_colors)
+        .map(|i| {
+            let (hue_index, shade) = match style.order {
+                ColorOrder::Interleaved => (i / 2, i % 2),
+                ColorOrder::Grouped if i < nb_darks => (i, 0),
+                ColorOrder::Grouped => (i - nb_darks, 1),
+            };
+            let hue = f32::from(hue_index) / f32::from(nb_darks) * style.hue_span;
+            let hue = (style.hue_offset + hue).rem_euclid(360.);
+            let lightness = style.lightness[usize::from(shade)];
+            let color: Srgb = match style.color_space {
+                ColorSpace::Hsl => {
+                    Srgb::from_color(Hsl::new_srgb(hue, style.saturation, lightness))
+                }
+                // `from_color` clamps whatever falls outside of sRGB.
+                ColorSpace::Lch => {
+                    Srgb::from_color(Lch::new(lightness * 100., style.saturation * 40., hue))
+                }
+            };
+            let (red, green, blue) = color.into_format().into_components();
+            Rgb32(u32::from_le_bytes([red, green, blue, 0xFF]))
+        })
+        .collect()
+}
+
+/// Generates `nb_colors` shades of grey, evenly spaced from black to white.
+///
+/// Since there are at most 256 colours, no two of them will be the same.
+pub fn build_grayscale_palette(nb_colors: u8) -> Vec<Rgb32> {
+    let max_index = u16::from(nb_colors.saturating_sub(1)).max(1);
+    (0..nb_colors)
+        .map(|i| {
+            let gray = (u16::from(i) * 255 / max_index) as u8;
+            Rgb32(u32::from_le_bytes([gray, gray, gray, 0xFF]))
+        })
+        .collect()
+}
+
+/// Consoles whose colour depth we know about.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Console {
+    /// The Game Boy Color, which stores colours as BGR555.
+    Gbc,
+}
+
+impl Console {
+    pub const ALL: [Self; 1] = [Self::Gbc];
+
+    /// The name by which the console is selected on the command line.
+    pub fn name(self) -> &'static str {
+        match self {
+            Self::Gbc => "gbc",
+        }
+    }
+
+    /// Converts a colour to the console's native format, the way a typical converter would.
+    pub fn quantize(self, color: Rgb32) -> u16 {
+        match self {
+            Self::Gbc => {
+                let [red, green, blue, _alpha] = color.0.to_le_bytes();
+                u16::from(red >> 3) | u16::from(green >> 3) << 5 | u16::from(blue >> 3) << 10
+            }
+        }
+    }
+}
+
+impl FromStr for Console {
+    type Err = UnknownConsole;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Self::ALL
+            .into_iter()
+            .find(|console| console.name() == s)
+            .ok_or_else(|| UnknownConsole(s.to_owned()))
+    }
+}
+
+/// Returned when parsing a [`Console`] whose name we don't recognise.
+#[derive(Debug)]
+pub struct UnknownConsole(String);
+
+impl Display for UnknownConsole {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "Unknown console \"{}\" (expected one of: ", self.0)?;
+        for (i, console) in Console::ALL.iter().enumerate() {
+            if i != 0 {
+                write!(f, ", ")?;
+            }
+            write!(f, "{}", console.name())?;
+        }
+        write!(f, ")")
+    }
+}
+
+/// Nudges the palette's colours so that none of them become identical once converted for `console`.
+///
+/// Colours that collide with an earlier one get their lightness, then their hue, shifted a little,
+/// until a free spot is found.
+/// If none can be found (e.g. there are only 32 shades of grey on the GBC), an error is returned.
+pub fn make_distinct(palette: &mut [Rgb32], console: Console) -> Result<(), IndistinctColor> {
+    // One 5-bit step is 8/255 of a channel's range; this is a little less than half of that.
+    const LIGHTNESS_STEP: f32 = 0.015;
+    const MAX_LIGHTNESS_STEPS: i8 = 10;
+    const HUE_STEP: f32 = 5.;
+    const MAX_HUE_STEPS: i8 = 6;
+
+    let mut taken = Vec::with_capacity(palette.len());
+    for (index, color) in palette.iter_mut().enumerate() {
+        if !taken.contains(&console.quantize(*color)) {
+            taken.push(console.quantize(*color));
+            continue;
+        }
+
+        let [red, green, blue, alpha] = color.0.to_le_bytes();
+        let hsl = Hsl::from_color(Srgb::new(red, green, blue).into_format::<f32>());
+        // Try small nudges first, alternating between both directions.
+        let offsets = |max_steps| (1..=max_steps).flat_map(|steps: i8| [steps, -steps]);
+        let nudged = std::iter::once(0)
+            .chain(offsets(MAX_HUE_STEPS))
+            .flat_map(|hue_steps| {
+                offsets(MAX_LIGHTNESS_STEPS).map(move |lightness_steps| {
+                    let mut nudged = hsl;
+                    nudged.hue += f32::from(hue_steps) * HUE_STEP;
+                    nudged.lightness = (nudged.lightness
+                        + f32::from(lightness_steps) * LIGHTNESS_STEP)
+                        .clamp(0., 1.);
+                    let (red, green, blue) =
+                        Srgb::from_color(nudged).into_format().into_components();
+                    Rgb32(u32::from_le_bytes([red, green, blue, alpha]))
+                })
+            })
+            .find(|nudged| !taken.contains(&console.quantize(*nudged)))
+            .ok_or(IndistinctColor { index, console })?;
+
+        *color = nudged;
+        taken.push(console.quantize(nudged));
+    }
+    Ok(())
+}
+
+/// Returned by [`make_distinct`] when a colour could not be told apart from the previous ones.
+#[derive(Debug)]
+pub struct IndistinctColor {
+    /// The index of the offending colour in the palette.
+    pub index: usize,
+    pub console: Console,
+}
+
+impl Display for IndistinctColor {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "Colour #{} cannot be made distinct from the previous ones on the {}",
+            self.index,
+            self.console.name(),
+        )
+    }
+}
+
+impl std::error::Error for IndistinctColor {}
+
+/// Reads a palette from a file containing one `#RRGGBB` colour per line.
+///
+/// Blank lines, and lines consisting of a `#` followed by whitespace (or nothing), are ignored.
+/// Only the first `nb_colors` colours are used; it is an error for the file to contain fewer.
+/// Colours may be followed by a name, which is ignored; see [`read_named_palette_file`].
+pub fn read_palette_file(path: &Path, nb_colors: u8) -> Result<Vec<Rgb32>, PaletteFileError> {
+    read_named_palette_file(path, nb_colors).map(|(palette, _)| palette)
+}
+
+/// Like [`read_palette_file`], but also returns the name given to each colour, if any.
+///
+/// A colour's name is whatever follows it on its line (e.g. `#FF0000 lock A`), minus surrounding whitespace.
+pub fn read_named_palette_file(
+    path: &Path,
+    nb_colors: u8,
+) -> Result<(Vec<Rgb32>, Vec<Option<String>>), PaletteFileError> {
+    let contents = std::fs::read_to_string(path).map_err(PaletteFileError::Io)?;
+
+    let mut palette = Vec::with_capacity(nb_colors.into());
+    let mut names = Vec::with_capacity(nb_colors.into());
+    for (i, line) in contents.lines().enumerate() {
+        let line = line.trim();
+        let Some(hex) = line.strip_prefix('#') else {
+            if line.is_empty() {
+                continue;
+            }
+            return Err(PaletteFileError::BadLine(i + 1));
+        };
+        if hex.chars().next().is_none_or(char::is_whitespace) {
+            continue; // This is a comment.
+        }
+
+        let (hex, name) = hex.split_once(char::is_whitespace).unwrap_or((hex, ""));
+        if hex.len() != 6 || !hex.bytes().all(|c| c.is_ascii_hexdigit()) {
+            return Err(PaletteFileError::BadLine(i + 1));
+        }
+        let rgb = u32::from_str_radix(hex, 16).unwrap(); // Can't fail, we just checked the digits.
+        let [blue, green, red, _] = rgb.to_le_bytes();
+        palette.push(Rgb32(u32::from_le_bytes([red, green, blue, 0xFF])));
+        let name = name.trim();
+        names.push((!name.is_empty()).then(|| name.to_owned()));
+    }
+
+    if palette.len() < nb_colors.into() {
+        return Err(PaletteFileError::TooFewColors {
+            expected: nb_colors,
+            found: palette.len(),
+        });
+    }
+    palette.truncate(nb_colors.into());
+    names.truncate(nb_colors.into());
+    Ok((palette, names))
+}
+
+/// The reasons why [`read_palette_file`] and [`read_named_palette_file`] can fail.
+#[derive(Debug)]
+pub enum PaletteFileError {
+    /// The file could not be read.
+    Io(std::io::Error),
+    /// The line with that (1-based) number is neither a colour nor a comment.
+    BadLine(usize),
+    /// The file does not define enough colours for the selected strategy.
+    TooFewColors { expected: u8, found: usize },
+}
+
+impl Display for PaletteFileError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Io(err) => write!(f, "{err}"),
+            Self::BadLine(line_no) => write!(f, "Line {line_no} is not a `#RRGGBB` colour"),
+            Self::TooFewColors { expected, found } => {
+                write!(f, "Expected {expected} colours, but found only {found}")
+            }
+        }
+    }
+}
+
+impl std::error::Error for PaletteFileError {}
+
+/// Reads an order to [`permute`][Attack::permute] `nb_tiles` tiles in, from a file containing one tile index per line.
+///
+/// Blank lines, and lines starting with a `#`, are ignored. Each index in `0..nb_tiles` must appear exactly once.
+pub fn read_tile_order_file(
+    path: &Path,
+    nb_tiles: usize,
+) -> Result<Vec<usize>, TileOrderFileError> {
+    let contents = std::fs::read_to_string(path).map_err(TileOrderFileError::Io)?;
+
+    let mut order = Vec::with_capacity(nb_tiles);
+    let mut seen = vec![false; nb_tiles];
+    for (i, line) in contents.lines().enumerate() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        let index: usize = line
+            .parse()
+            .map_err(|_| TileOrderFileError::BadLine(i + 1))?;
+        match seen.get_mut(index) {
+            None => {
+                return Err(TileOrderFileError::OutOfRange {
+                    line_no: i + 1,
+                    index,
+                    nb_tiles,
+                })
+            }
+            Some(true) => {
+                return Err(TileOrderFileError::Duplicate {
+                    line_no: i + 1,
+                    index,
+                })
+            }
+            Some(seen) => *seen = true,
+        }
+        order.push(index);
+    }
+
+    // Since there are no duplicates, a missing index is the same as too few lines.
+    if let Some(index) = seen.iter().position(|&seen| !seen) {
+        return Err(TileOrderFileError::Missing(index));
+    }
+    Ok(order)
+}
+
+/// The reasons why [`read_tile_order_file`] can fail.
+#[derive(Debug)]
+pub enum TileOrderFileError {
+    /// The file could not be read.
+    Io(std::io::Error),
+    /// The line with that (1-based) number is neither a tile index nor a comment.
+    BadLine(usize),
+    /// The line with that number refers to a tile that doesn't exist.
+    OutOfRange {
+        line_no: usize,
+        index: usize,
+        nb_tiles: usize,
+    },
+    /// The line with that number refers to a tile that was already listed.
+    Duplicate { line_no: usize, index: usize },
+    /// The file doesn't list the tile with that index.
+    Missing(usize),
+}
+
+impl Display for TileOrderFileError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Io(err) => write!(f, "{err}"),
+            Self::BadLine(line_no) => write!(f, "Line {line_no} is not a tile index"),
+            Self::OutOfRange {
+                line_no,
+                index,
+                nb_tiles,
+            } => write!(
+                f,
+                "Line {line_no} refers to tile #{index}, but there are only {nb_tiles} tiles"
+            ),
+            Self::Duplicate { line_no, index } => {
+                write!(f, "Line {line_no} lists tile #{index} again")
+            }
+            Self::Missing(index) => write!(f, "Tile #{index} is missing from the order"),
+        }
+    }
+}
+
+impl std::error::Error for TileOrderFileError {}
+
+/// Writes a list of palettes to `path`, as a JSON array of arrays of colour indices (see [`palettes_json`]).
+pub fn write_palettes_json(palettes: &[Vec<u8>], path: &Path) -> std::io::Result<()> {
+    std::fs::write(path, palettes_json(palettes))
+}
+
+/// Formats a list of palettes as a JSON array of arrays of colour indices.
+pub fn palettes_json(palettes: &[Vec<u8>]) -> String {
+    let mut json = String::new();
+    spec::push_index_lists(&mut json, palettes.iter().map(Vec::as_slice));
+    json.push('\n');
+    json
+}
+
+/// Generates tiles that contain the specified colours and none else, in a single column, and returns the image
+/// instead of storing it; e.g. to check its pixels directly.
+///
+/// This is a shorthand for [`ImageBuilder::build_in_memory`] with its defaults, which can be used to tweak the rest.
+///
+/// ```
+/// use no_packsaran::{build_palette, plumers::prelude::*, render_tiles, PaletteStyle, ProtoPalette};
+///
+/// let palette = build_palette(4, PaletteStyle::default());
+/// let tiles = [ProtoPalette::new(vec![0, 1]), ProtoPalette::new(vec![2, 3])];
+/// let image = render_tiles(8, 8, &palette, &tiles)?;
+/// // Without shuffling, each tile's pixels cycle through its colours, and the second tile is below the first.
+/// assert_eq!(image.frame(0)[(1, 0)], 1);
+/// assert_eq!(image.frame(0)[(0, 8)], 2);
+/// # Ok::<(), std::io::Error>(())
+/// ```
+pub fn render_tiles<Tiles: IntoIterator<Item = Tile>, Tile: Borrow<ProtoPalette>>(
+    tile_width: usize,
+    tile_height: usize,
+    palette: &[Rgb32],
+    tile_colors: Tiles,
+) -> std::io::Result<PalettedImage32>
+where
+    Tiles::IntoIter: Clone,
+{
+    ImageBuilder::new()
+        .tile_size(tile_width, tile_height)
+        .palette(palette)
+        .tiles(tile_colors)
+        .build_in_memory()
+}
+
+/// Generates tiles that contain the specified colours and none else, and writes them to `dest`
+/// (e.g. a `&Path`, or a [`plumers::image::Output`] wrapping any `Write`r).
+///
+/// This is a shorthand for going through an [`ImageBuilder`], whose documentation details how the image is laid out.
+///
+/// Returns how many bytes were written.
+#[allow(clippy::too_many_arguments)] // They're all orthogonal knobs, bundling them wouldn't make this clearer.
+pub fn gen_image<Tiles: IntoIterator<Item = Tile>, Tile: Borrow<ProtoPalette>, Dest: ImageDest>(
+    tile_width: usize,
+    tile_height: usize,
+    columns: usize,
+    nb_frames: usize,
+    palette: &[Rgb32],
+    tile_colors: Tiles,
+    fill: TileFill,
+    format: ImageFormat,
+    dest: Dest,
+) -> std::io::Result<NonZeroUsize>
+where
+    Tiles::IntoIter: Clone,
+{
+    ImageBuilder::new()
+        .tile_size(tile_width, tile_height)
+        .columns(columns)
+        .frames(nb_frames)
+        .palette(palette)
+        .tiles(tile_colors)
+        .fill(fill)
+        .format(format)
+        .build_and_store(dest)
+}
+
+/// The ways that the palette's swatches can be laid out in its preview.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum PreviewLayout {
+    /// A single row of 1-pixel swatches; see [`gen_palette_preview`].
+    #[default]
+    Strip,
+    /// Rows of larger swatches; see [`gen_palette_grid`].
+    Grid,
+}
+
+impl PreviewLayout {
+    pub const ALL: [Self; 2] = [Self::Strip, Self::Grid];
+
+    /// The name by which the layout is selected on the command line.
+    pub fn name(self) -> &'static str {
+        match self {
+            Self::Strip => "strip",
+            Self::Grid => "grid",
+        }
+    }
+}
+
+impl FromStr for PreviewLayout {
+    type Err = UnknownPreviewLayout;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Self::ALL
+            .into_iter()
+            .find(|layout| layout.name() == s)
+            .ok_or_else(|| UnknownPreviewLayout(s.to_owned()))
+    }
+}
+
+/// Returned when parsing a [`PreviewLayout`] whose name we don't recognise.
+#[derive(Debug)]
+pub struct UnknownPreviewLayout(String);
+
+impl Display for UnknownPreviewLayout {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "Unknown preview layout \"{}\" (expected one of: ",
+            self.0
+        )?;
+        for (i, layout) in PreviewLayout::ALL.iter().enumerate() {
+            if i != 0 {
+                write!(f, ", ")?;
+            }
+            write!(f, "{}", layout.name())?;
+        }
+        write!(f, ")")
+    }
+}
+
+/// Writes a swatch of the palette to `dest`: an image one pixel tall, whose `i`th pixel is colour `i`.
+///
+/// Returns how many bytes were written.
+pub fn gen_palette_preview<Dest: ImageDest>(
+    palette: &[Rgb32],
+    dest: Dest,
+) -> std::io::Result<NonZeroUsize> {
+    let mut img = PalettedImage32::new_zeroed(
+        ImageFormat::Png,
+        AlphaMode::ZeroIsTransparent,
+        1,
+        palette.len(),
+        1,
+        palette.iter().copied(),
+    )
+    .unwrap();
+    let mut frame = img.frame_mut(0);
+    for i in 0..palette.len() {
+        frame[(i, 0)] = i as u8;
+    }
+
+    img.store(dest)
+}
+
+/// Writes a grid of the palette's swatches to `dest`, `columns` of them per row: swatch `i` is a solid square
+/// of `swatch_size` pixels of colour `i`, placed like the `i`th tile of an image made by [`ImageBuilder`].
+/// Cells past the last colour are left with colour 0.
+///
+/// Returns how many bytes were written.
+pub fn gen_palette_grid<Dest: ImageDest>(
+    palette: &[Rgb32],
+    swatch_size: usize,
+    columns: usize,
+    dest: Dest,
+) -> std::io::Result<NonZeroUsize> {
+    // A single-colour tile is solid, however it's filled.
+    let swatches = (0..palette.len()).map(|i| ProtoPalette::new(vec![i as u8]));
+    ImageBuilder::new()
+        .tile_size(swatch_size, swatch_size)
+        .columns(columns)
+        .palette(palette)
+        .tiles(swatches)
+        .build_and_store(dest)
+}
+
+/// Lazily iterates through the `k`-combinations of `0..n`, in lexicographic order.
+///
+/// (Unlike `combination::combine::index`, which returns all of them in one big `Vec`.)
+#[derive(Debug, Clone)]
+struct Combinations {
+    n: usize,
+    /// The next combination to be returned, or `None` once all have been.
+    indices: Option<Vec<usize>>,
+}
+
+impl Combinations {
+    fn new(n: usize, k: usize) -> Self {
+        Self {
+            n,
+            indices: (k <= n).then(|| (0..k).collect()),
+        }
+    }
+}
+
+impl Iterator for Combinations {
+    type Item = Vec<usize>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let indices = self.indices.as_mut()?;
+        let combination = indices.clone();
+
+        // Find the rightmost index that can still be incremented, and reset all of the ones after it.
+        let k = indices.len();
+        match (0..k).rev().find(|&i| indices[i] < self.n - k + i) {
+            Some(i) => {
+                indices[i] += 1;
+                for j in i + 1..k {
+                    indices[j] = indices[j - 1] + 1;
+                }
+            }
+            None => self.indices = None,
+        }
+
+        Some(combination)
+    }
+}
+
+/// How a tile's colours get spread across its pixels.
+///
+/// Every colour of the tile's proto-palette is used at least once, as long as the tile has enough pixels.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct TileFill {
+    /// Which pixels get which of the tile's colours.
+    pub pattern: FillPattern,
+    /// If set, each tile's pixels are then shuffled around (reproducibly, using this seed).
+    pub seed: Option<u64>,
+}
+
+/// The layouts that [`TileFill`] can spread a tile's colours in, before any shuffling.
+///
+/// Every one of them uses each colour at least once, as long as the tile has as many pixels as colours.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum FillPattern {
+    /// The pixels cycle through the tile's colours in order, row by row.
+    #[default]
+    Modulo,
+    /// Each colour takes up one run of (about) as many pixels as the others, one after the other, row by row.
+    Sequential,
+    /// Each diagonal of pixels gets a single colour, cycling through them like [`Modulo`][Self::Modulo], making stripes.
+    /// Tiles with more colours than diagonals use [`Modulo`][Self::Modulo] instead, since stripes couldn't show them all.
+    Diagonal,
+}
+
+impl FillPattern {
+    pub const ALL: [Self; 3] = [Self::Modulo, Self::Sequential, Self::Diagonal];
+
+    /// The name by which the pattern is selected on the command line.
+    pub fn name(self) -> &'static str {
+        match self {
+            Self::Modulo => "modulo",
+            Self::Sequential => "sequential",
+            Self::Diagonal => "diagonal",
+        }
+    }
+}
+
+impl FromStr for FillPattern {
+    type Err = UnknownFillPattern;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Self::ALL
+            .into_iter()
+            .find(|pattern| pattern.name() == s)
+            .ok_or_else(|| UnknownFillPattern(s.to_owned()))
+    }
+}
+
+/// Returned when parsing a [`FillPattern`] whose name we don't recognise.
+#[derive(Debug)]
+pub struct UnknownFillPattern(String);
+
+impl Display for UnknownFillPattern {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "Unknown tile fill \"{}\" (expected one of: ", self.0)?;
+        for (i, pattern) in FillPattern::ALL.iter().enumerate() {
+            if i != 0 {
+                write!(f, ", ")?;
+            }
+            write!(f, "{}", pattern.name())?;
+        }
+        write!(f, ")")
+    }
+}
+
+impl TileFill {
+    /// Which of its colours each of the tile's pixels (in row-major order) should be; empty tiles have none.
+    ///
+    /// `tile_index` is the tile's position in the image, so that tiles with the same colours
+    /// don't all get shuffled the same way.
+    pub fn slots(
+        &self,
+        tile_index: usize,
+        nb_tile_colors: usize,
+        tile_width: usize,
+        tile_height: usize,
+    ) -> Vec<usize> {
+        if nb_tile_colors == 0 {
+            return vec![];
+        }
+        let nb_pixels = tile_width * tile_height;
+        let mut slots: Vec<_> = match self.pattern {
+            FillPattern::Sequential => (0..nb_pixels)
+                .map(|i| i * nb_tile_colors / nb_pixels)
+                .collect(),
+            FillPattern::Diagonal if nb_tile_colors < tile_width + tile_height => (0..nb_pixels)
+                .map(|i| (i % tile_width + i / tile_width) % nb_tile_colors)
+                .collect(),
+            // Tiles with more colours than diagonals can't show them all as stripes.
+            FillPattern::Modulo | FillPattern::Diagonal => {
+                (0..nb_pixels).map(|i| i % nb_tile_colors).collect()
+            }
+        };
+        if let Some(seed) = self.seed {
+            // Shuffling keeps how many times each colour is used, so all of them are still there.
+            let mut rng = SplitMix64(seed ^ (tile_index as u64).wrapping_mul(SplitMix64::GAMMA));
+            for i in (1..slots.len()).rev() {
+                slots.swap(i, rng.below(i + 1));
+            }
+        }
+        slots
+    }
+
+    /// The colour index of each of the tile's pixels (in row-major order); empty tiles have none.
+    pub fn pixels(
+        &self,
+        tile_index: usize,
+        colors: &[u8],
+        tile_width: usize,
+        tile_height: usize,
+    ) -> Vec<u8> {
+        if colors.is_empty() {
+            return vec![];
+        }
+        self.slots(tile_index, colors.len(), tile_width, tile_height)
+            .into_iter()
+            .map(|slot| colors[slot])
+            .collect()
+    }
+}
+
+/// A tiny PRNG, so that shuffling doesn't require pulling in a whole crate.
+/// (See <https://prng.di.unimi.it/splitmix64.c>.)
+struct SplitMix64(u64);
+
+impl SplitMix64 {
+    const GAMMA: u64 = 0x9E37_79B9_7F4A_7C15;
+
+    fn next_u64(&mut self) -> u64 {
+        self.0 = self.0.wrapping_add(Self::GAMMA);
+        let mut z = self.0;
+        z = (z ^ (z >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+        z ^ (z >> 31)
+    }
+
+    /// A number in `0..n`. (The modulo bias is negligible for the small `n`s we use.)
+    fn below(&mut self, n: usize) -> usize {
+        (self.next_u64() % n as u64) as usize
+    }
+}
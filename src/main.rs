@@ -17,185 +17,1942 @@
 */
 
 use std::{
+    io::Write,
+    num::NonZeroUsize,
     path::{Path, PathBuf},
     process::ExitCode,
+    sync::atomic::{AtomicU8, Ordering},
 };
 
-use palette::{FromColor, Hsl, Srgb};
-use plumers::prelude::*;
+use no_packsaran::{
+    build_grayscale_palette, build_palette, defeat_best_fusion_locked_at, defeat_combined,
+    defeat_mixed, defeat_single, defeat_targeting, encode_1bpp, encode_2bpp, encode_4bpp,
+    encode_bgr555_palette, encode_c_header, encode_gbc_2bpp, encode_gbc_attrmap,
+    encode_gbc_palettes, encode_rgbds_asm, gen_palette_grid, gen_palette_preview, make_distinct,
+    nb_tiles, pack_greedy, pack_mixed, pack_optimal, palette_size_for_target, palettes_json,
+    plumers::{
+        image::{ImageDest, ImageFormat, Output},
+        prelude::Rgb32,
+    },
+    read_manifest_file, read_named_palette_file, read_spec_file, read_tile_order_file,
+    round_down_to_even, spec_json, worst_case_palettes, write_palettes_json, write_spec_json,
+    Attack, CombineError, Console, FillPattern, ImageBuilder, Job, OutputFormat, PaletteStyle,
+    PixelOrder, PreviewLayout, ProtoPalette, Spec, Strategy, TileFill, TileOrder,
+};
+
+/// How much gets printed to stderr, set once by `--quiet` or `--verbose`.
+static VERBOSITY: AtomicU8 = AtomicU8::new(Verbosity::Normal as u8);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+#[repr(u8)]
+enum Verbosity {
+    /// Only errors.
+    Quiet,
+    /// Errors, and whatever was asked for (e.g. `--stats`).
+    Normal,
+    /// Also details about what is being done, for debugging.
+    Verbose,
+}
+
+fn verbosity_is_at_least(level: Verbosity) -> bool {
+    VERBOSITY.load(Ordering::Relaxed) >= level as u8
+}
+
+/// Errors are always printed, even with `--quiet`.
+macro_rules! error {
+    ($($arg:tt)*) => {
+        eprintln!("Error: {}", format_args!($($arg)*))
+    };
+}
+
+macro_rules! info {
+    ($($arg:tt)*) => {
+        if verbosity_is_at_least(Verbosity::Normal) {
+            eprintln!($($arg)*);
+        }
+    };
+}
+
+macro_rules! debug {
+    ($($arg:tt)*) => {
+        if verbosity_is_at_least(Verbosity::Verbose) {
+            eprintln!($($arg)*);
+        }
+    };
+}
+
+xflags::xflags! {
+    /// Generator of images to defeat palette packing algorithms.
+    cmd no-packsaran {
+            /// How large your colour palettes are. Defaults to 4 (2bpp).
+            optional -s,--palette-size nb_colors: usize
+            /// Comma-separated sizes of palettes that don't all hold as many colours (e.g. `4,4,16`),
+            /// to defeat first-fit over them in that order instead of a strategy.
+            /// The strategy and `--palette-size` must then be omitted, so the output path comes first.
+            optional --palette-sizes sizes: String
+            /// How many entries of each palette the tiles can't use, e.g. because colour 0 is reserved for transparency.
+            /// The tiles are then crafted for palettes that much smaller (including each of `--palette-sizes`),
+            /// so that it's the usable part of the palettes that gets overflowed: e.g. `-s 16 --reserved-per-palette 1`
+            /// targets 15-colour palettes (rounded down to 14, like odd sizes always are). Defaults to 0.
+            optional --reserved-per-palette nb_entries: usize
+            /// How many palettes to force the strategy's packer into, picking the smallest (even) palette size that does so
+            /// instead of `--palette-size`. `any_fit` gets there by keeping only as many of its pairs of tiles,
+            /// each of which fills up a palette, so any target of at least 3 works; the other strategies force a number
+            /// of palettes that only depends on the palette size (see `--explain`), so only those are reachable.
+            /// `combined` doesn't support this.
+            optional --target-palettes nb_palettes: usize
+            /// How large your tiles are, both horizontally and vertically. Defaults to 8.
+            /// Image formats and `c-header` accept any size, but `1bpp`, `2bpp`, `4bpp`, and `rgbds-asm` require multiples of 8,
+            /// splitting larger tiles into 8x8 ones.
+            optional -T,--tile-size pixels: usize
+            /// How wide your tiles are. Overrides `--tile-size`.
+            optional --tile-width pixels: usize
+            /// How tall your tiles are. Overrides `--tile-size`.
+            optional --tile-height pixels: usize
+            /// How many tiles wide the image should be. Defaults to 1.
+            optional --columns nb_tiles: usize
+            /// Exact number of tiles across the image, e.g. to match a screen; requires `--height-in-tiles`.
+            /// The grid is then fixed regardless of how many tiles there are, overriding `--columns`:
+            /// it's an error if they don't all fit, and leftover cells are filled with `--background-index`.
+            optional --width-in-tiles nb_tiles: usize
+            /// Exact number of tiles down the image; requires `--width-in-tiles`.
+            optional --height-in-tiles nb_tiles: usize
+            /// File to read the image's colours from, one `#RRGGBB` per line, optionally followed by a name
+            /// (which ends up in `--emit-spec`'s output). Defaults to generating a rainbow.
+            optional --palette-file path: PathBuf
+            /// Use shades of grey instead of a rainbow. (Ignored if `--palette-file` is given.)
+            optional --grayscale
+            /// Saturation of the rainbow's colours (chroma, in LCh), between 0 and 1. Defaults to 1.
+            optional --saturation saturation: f32
+            /// Hue of the rainbow's first colour, in degrees. Defaults to 0 (red).
+            optional --hue-offset degrees: f32
+            /// How much of the colour wheel the rainbow spans, in degrees, up to 360. Defaults to 360.
+            optional --hue-span degrees: f32
+            /// Lightness of the rainbow's dark and bright colours, as two comma-separated numbers
+            /// between 0 and 1. Defaults to `0.25,0.75`.
+            optional --light-dark lightnesses: String
+            /// How to lay out the rainbow's colours: `interleaved` (dark and bright colours alternate, the default),
+            /// or `grouped` (all dark colours, then all bright ones).
+            optional --color-order order: String
+            /// Colour space to spread the rainbow's hues across: `hsl` (the default), or `lch`,
+            /// whose colours look more evenly spaced, but are less vivid.
+            optional --color-space space: String
+            /// Console whose colour depth should be accounted for, so that no two colours become identical.
+            /// Only `gbc` is supported.
+            optional --target console: String
+            /// Also write the optimal palettes for the generated tiles to this file, as JSON.
+            optional --emit-expected path: PathBuf
+            /// Also write a description of the attack (strategy, sizes, tiles, optimal palettes,
+            /// and the names of `--palette-file`'s colours) to this file, as JSON.
+            optional --emit-spec path: PathBuf
+            /// Also write a GBC attribute map to this file: for each tile, one byte selecting its optimal palette.
+            /// Meant to go along with `--format 2bpp`, whose tiles are in the same order, and `--emit-pal`:
+            /// both then use the optimal palettes too, so that the three can be loaded onto hardware as-is.
+            optional --emit-attrmap path: PathBuf
+            /// Also write the image's colours to this file, as a GBC `.pal`: two bytes per colour, in index order
+            /// (each a little-endian BGR555 word, i.e. `0bbbbbgggggrrrrr`). At most 32 colours fit, the GBC's 8 palettes of 4.
+            /// With `--emit-attrmap`, the optimal palettes are written instead, in order and each padded to 4 colours.
+            optional --emit-pal path: PathBuf
+            /// Also write the image's colours to this file, as a PNG one pixel tall.
+            optional --preview-palette path: PathBuf
+            /// How `--preview-palette` lays out the colours: `strip` (the default), a row of single pixels,
+            /// or `grid`, rows of larger swatches that are easier to make out in large palettes.
+            optional --palette-preview-layout layout: String
+            /// How many pixels wide and tall each swatch of the `grid` layout is. Defaults to 8.
+            optional --preview-swatch-size pixels: usize
+            /// How many swatches each row of the `grid` layout holds. Defaults to 16.
+            optional --preview-columns nb_swatches: usize
+            /// Also write a zip archive to this file, holding everything needed to share the test case: the image
+            /// (`image.<format>`), its spec (`spec.json`, like `--emit-spec`), the optimal palettes (`expected.json`,
+            /// like `--emit-expected`), and the palette preview (`palette.png`, like `--preview-palette`).
+            /// The archive only depends on its contents, so the same options always make the same bytes.
+            /// Requires building with the `bundle` feature.
+            optional --bundle path: PathBuf
+            /// Format to write the image in: `png` (the default), `rgb-png`, `apng`, `gif`, `bmp`, `1bpp`, `2bpp`,
+            /// `4bpp`, `c-header`, or `rgbds-asm`. `1bpp` only fits palettes of 2 colours, e.g. `-s 2 any_fit`.
+            /// `rgb-png` stores each pixel's colour instead of its index, for tools that can't read paletted images;
+            /// which pixels share an index is then lost, so it's only good for looking at.
+            optional --format format: String
+            /// How many frames the image should have, each rotating the tiles by one more position.
+            /// Requires the `apng` or `gif` format if more than 1. Defaults to 1.
+            optional --frames nb_frames: usize
+            /// Upscale the image by this factor, making each pixel a square of that many pixels, e.g. for slides.
+            /// Only image formats can be scaled. Defaults to 1.
+            optional --scale factor: usize
+            /// Refuse to generate images wider or taller than this (after scaling). Defaults to 16384.
+            optional --max-dimension pixels: usize
+            /// Refuse to generate more tiles than this (including `--repeat`ed ones), as they may not fit in memory.
+            /// Defaults to 16777216.
+            optional --max-tiles nb_tiles: usize
+            /// Refuse to generate tiles that use more colours than this in total, e.g. 32 for the GBC's 8 background palettes of 4.
+            /// Strategies use several palettes' worth of colours (usually two, or more for those that repeat their gadgets),
+            /// so this is typically a multiple of `--palette-size`. Defaults to 256, which any image's colours fit in.
+            optional --max-colors nb_colors: usize
+            /// Name of the array in `c-header` output. Defaults to `evil_tiles`.
+            optional --array-name name: String
+            /// Repeat the whole list of tiles this many times, e.g. to stress-test packers.
+            /// This multiplies both the tile count and the image's size. Defaults to 1.
+            optional --repeat times: usize
+            /// Colour index to give the pixels not covered by any tile (e.g. at the end of the last row). Defaults to 0.
+            optional --background-index index: u8
+            /// Make the colour with this index fully transparent (alpha 0), and all others opaque (alpha `0xFF`);
+            /// or `none` (the default) to keep every colour opaque. PNGs are always stored indexed (with a `PLTE` chunk),
+            /// and a transparent index makes them get a `tRNS` chunk as well, for loaders that rely on it.
+            /// Requires `png`, `apng`, or `gif`, unless `none`.
+            optional --alpha-index index: String
+            /// Print how many tiles are pixel-for-pixel identical to an earlier one.
+            /// Packers that deduplicate tiles only see one of each, which can weaken the attack.
+            optional --dedup-report
+            /// Rearrange the pixels of duplicate tiles (keeping their colours), so that deduplicating packers see them all.
+            optional --force-unique
+            /// Draw each tile's index in its corner, in an extra colour added to the palette, e.g. for presentations.
+            /// The labels' colour ends up in every tile, so the image no longer defeats anything!
+            optional --annotate
+            /// Shuffle each tile's pixels around, reproducibly, using this seed.
+            /// Defaults to leaving them as `--tile-fill` laid them out.
+            optional --seed seed: u64
+            /// How each tile's colours are laid out (before `--seed` shuffles them): `modulo` (the default),
+            /// `sequential`, or `diagonal`.
+            optional --tile-fill pattern: String
+            /// How busy the tiles look, e.g. to stress run-length-based tile compressors: `full` (every pixel differs from the
+            /// previous one, i.e. `--tile-fill modulo`) or `sparse` (each colour in one solid band, i.e. `--tile-fill sequential`).
+            /// Only the pixels' arrangement changes, never which colours each tile has.
+            optional --density density: String
+            /// Order that the raw formats (`1bpp`, `2bpp`, `4bpp`, `rgbds-asm`, and `c-header`) list each tile's pixels in:
+            /// `row` (the default, row by row) or `column` (column by column, each top to bottom), for loaders that expect
+            /// transposed tiles. Image formats always store rows, so they ignore this.
+            optional --pixel-order order: String
+            /// Also shuffle the order of the tiles, using `--seed` (which is then required, and still shuffles their pixels).
+            /// Most strategies rely on the order that they emit tiles in, so this may well keep them from working;
+            /// `--verify` reports how many palettes are needed in both orders, to tell.
+            optional --shuffle-order
+            /// Order to emit the tiles in: `crafted` (the strategy's own, the default), `reverse`, or `sorted`
+            /// (by their sets of colours). Unlike `--shuffle-order`, this is deterministic;
+            /// `--verify` also reports how many palettes are needed in this order.
+            optional --ordering order: String
+            /// File listing the order to emit the tiles in, one index (into the strategy's order) per line,
+            /// e.g. to reproduce an order observed in the wild. Every tile must be listed exactly once
+            /// (counting `--repeat`'s copies); `--verify` also reports how many palettes are needed in this order.
+            optional --tile-order-file path: PathBuf
+            /// Emit the tiles in runs of this many from one of the two sets of colours, then as many from the other, and so on,
+            /// instead of alternating between them one by one; e.g. to probe packers that look a few tiles ahead.
+            /// Only `any_fit` and `next_fit` have such sets. Their attacks rely on each tile not fitting along
+            /// with the ones just before it, which only tiles of the other set ensure; so periods above 1 let tiles of the
+            /// same set share palettes. For `any_fit`, this undoes the attack entirely (first-fit then finds
+            /// the two sets' palettes right away); `next_fit` degrades more gradually, to about 2N / period palettes.
+            /// `--verify` reports how many palettes are needed in both orders. A period of 1 is the strategy's own order.
+            optional --interleave-every nb_tiles: usize
+            /// Overwrite the output file (and the `--bundle`) if it already exists.
+            optional -f,--force
+            /// Print statistics about the generated image to stderr.
+            optional --stats
+            /// Only print errors to stderr (this silences `--stats`, and the progress shown while drawing large images).
+            optional -q,--quiet
+            /// Also print what is being done to stderr, for debugging.
+            optional -v,--verbose
+            /// Only print how many tiles and colours would be generated, without writing anything.
+            optional --count-only
+            /// Print to stdout how the strategy built the tiles, step by step, followed by their proto-palettes
+            /// and how many palettes they should need. The image is still written, unless `--count-only` is passed as well.
+            optional --explain
+            /// Run the targeted packing algorithm on the generated tiles, and check that it needs more palettes than optimal.
+            /// If a check fails, the exit code is 2, unlike for other errors (bad arguments, I/O...), which exit with 1.
+            optional --verify
+            /// With `--verify`, also check that the packing algorithm needs at least this many palettes.
+            optional --min-palettes nb_palettes: usize
+            /// Brute-force the fewest palettes that the generated tiles fit in, and check that the optimal palettes are that many.
+            /// This takes exponential time, so it only supports a couple dozen distinct proto-palettes.
+            /// Like with `--verify`, the exit code is 2 if the check fails (but 1 if there are too many to brute-force).
+            optional --verify-optimal
+            /// Check that each of the colours is used by at least one tile, listing those that aren't.
+            /// Like with `--verify`, the exit code is 2 if the check fails.
+            optional --assert-all-colors-used
+            /// Generate the tiles listed in this JSON file, instead of a strategy's.
+            /// It must be of the form `{ "nb_colors": N, "tiles": [[0,1],[2,3]] }`;
+            /// the strategy must then be omitted, so the output path comes first.
+            optional --from-spec path: PathBuf
+            /// Print the available strategies, and what they do, then exit.
+            optional --list-strategies
+            /// Generate an image for every strategy that supports the palette size, instead of a single one.
+            /// Each is written to `--output-dir`, named after the strategy (e.g. `any_fit.png`);
+            /// the strategy and output path must then be omitted, as must the flags that check, annotate,
+            /// or write metadata about a single image (`--verify`, `--emit-pal`, and so on).
+            optional --all-strategies
+            /// Generate every image listed in this JSON file, instead of a single one. It must be an array of jobs,
+            /// each of the form `{ "strategy": "any_fit", "palette_size": 4, "tile_size": 8, "out_path": "a.png" }`,
+            /// where `out_path` is relative to the manifest, and the sizes default to `--palette-size` and `--tile-size`.
+            /// All other options apply to every job; the strategy and output path must then be omitted,
+            /// as must the flags that only make sense for a single image, like with `--all-strategies`.
+            /// A job failing doesn't stop the others, but they are all reported, and the exit code is then 1.
+            optional --manifest path: PathBuf
+            /// Directory to write the images of `--all-strategies` to.
+            optional --output-dir dir: PathBuf
+            /// Strategy to defeat. One of `any_fit`, `best_fusion`, `first_fit`, `worst_fit`,
+            /// `best_fit_decreasing`, `next_fit`, `dsatur`, `superfamiconv`, `overload_and_remove`,
+            /// `merge_smallest`, `obj`, `ffd_index_tiebreak`, `cluster_then_fit`, or `combined`.
+            /// Required unless `--list-strategies`, `--from-spec`, `--palette-sizes`, or `--compare` is passed.
+            optional strategy: String
+            /// Comma-separated strategies for `combined` to run. Defaults to `any_fit,best_fusion`.
+            optional --combine strategies: String
+            /// Two comma-separated strategies (e.g. `first_fit,worst_fit`) to render side by side in a single image,
+            /// each in its own grid `--columns` wide, with a column of `--background-index` in between.
+            /// Their tiles are generated like `combined`'s: the second strategy's colour indices start after the first's,
+            /// so the halves never share a colour, and each keeps its own optimal palettes.
+            /// The strategy must then be omitted, so the output path comes first.
+            optional --compare strategies: String
+            /// Where `best_fusion`'s two "locking" colours go, as two comma-separated indices (e.g. `0,1`);
+            /// the other colours keep their order, filling up the rest. Defaults to the last two indices.
+            optional --lock-indices indices: String
+            /// Where to write the Evil™ image to, or `-` for standard output.
+            /// (The latter must come after `--`, e.g. `no_packsaran any_fit -- -`.) Required unless `--count-only`, `--verify`, or `--verify-optimal` is passed.
+            optional out_path: PathBuf
+    }
+}
+
+/// Exit code for when `--verify` or `--verify-optimal` ran, but found the attack lacking;
+/// every other error (invalid arguments, I/O, and so on) exits with [`ExitCode::FAILURE`], i.e. 1,
+/// so that scripts can tell a meaningful result apart from a problem with the environment.
+const VERIFICATION_FAILED: u8 = 2;
 
 fn main() -> ExitCode {
-    let cli = xflags::parse_or_exit! {
-        /// How large your colour palettes are. Defaults to 4 (2bpp).
-        optional -s,--palette-size nb_colors: usize
-        /// How large your tiles are (they are assumed to be square). Defaults to 8.
-        optional -T,--tile-size pixels: usize
-        /// Strategy to defeat. Either `any_fit` or `best_fusion`.
-        required strategy: String
-        /// Where to write the Evil™ image to.
-        required out_path: PathBuf
-    };
-    let palette_size = cli.palette_size.unwrap_or(4);
-    let tile_size = cli.tile_size.unwrap_or(8);
-
-    let (tile_colors, nb_colors) = if cli.strategy == "any_fit" {
-        let nb_colors = palette_size & !2; // Round down to the nearest even number.
-        defeat_any_fit(nb_colors)
-    } else if cli.strategy == "best_fusion" {
-        let nb_colors = palette_size & !2; // Round down to the nearest even number.
-        defeat_best_fusion(nb_colors)
-    } else {
-        eprintln!("Error: Unknown strategy \"{}\"", cli.strategy);
+    let cli = match NoPacksaran::from_env() {
+        Ok(cli) => cli,
+        Err(err) if err.is_help() => {
+            println!("{err}");
+            return ExitCode::SUCCESS;
+        }
+        // `xflags` would exit with code 2 otherwise, which is reserved for failed verifications.
+        Err(err) => {
+            eprintln!("{err}");
+            return ExitCode::FAILURE;
+        }
+    };
+    let verbosity = match (cli.quiet, cli.verbose) {
+        (false, false) => Verbosity::Normal,
+        (true, false) => Verbosity::Quiet,
+        (false, true) => Verbosity::Verbose,
+        (true, true) => {
+            error!("`--quiet` and `--verbose` are mutually exclusive");
+            return ExitCode::FAILURE;
+        }
+    };
+    VERBOSITY.store(verbosity as u8, Ordering::Relaxed);
+
+    if cli.list_strategies {
+        let width = Strategy::ALL
+            .map(|strategy| strategy.name().len())
+            .into_iter()
+            .max();
+        for strategy in Strategy::ALL {
+            println!(
+                "{:width$}  {}",
+                strategy.name(),
+                strategy.description(),
+                width = width.unwrap_or(0)
+            );
+        }
+        return ExitCode::SUCCESS;
+    }
+
+    let sizes = match &cli.palette_sizes {
+        Some(_) if cli.palette_size.is_some() => {
+            error!("`--palette-size` and `--palette-sizes` are mutually exclusive");
+            return ExitCode::FAILURE;
+        }
+        Some(list) => match parse_palette_sizes(list) {
+            Some(sizes) => Some(sizes),
+            None => {
+                error!("`--palette-sizes` expects comma-separated numbers, not \"{list}\"");
+                return ExitCode::FAILURE;
+            }
+        },
+        None => None,
+    };
+    // The palette size is derived from the strategy, so this must happen before the latter is even checked.
+    let target_size = match cli.target_palettes {
+        None => None,
+        Some(_) if cli.palette_size.is_some() || sizes.is_some() => {
+            error!("`--target-palettes` picks the palette size, so it can't be combined with `--palette-size` nor `--palette-sizes`");
+            return ExitCode::FAILURE;
+        }
+        Some(_) if cli.reserved_per_palette.is_some() => {
+            error!("`--target-palettes` and `--reserved-per-palette` are mutually exclusive");
+            return ExitCode::FAILURE;
+        }
+        Some(_)
+            if cli.from_spec.is_some()
+                || cli.compare.is_some()
+                || cli.all_strategies
+                || cli.manifest.is_some() =>
+        {
+            error!("`--target-palettes` only makes sense when running a single strategy");
+            return ExitCode::FAILURE;
+        }
+        Some(nb_palettes) => match cli.strategy.as_deref().map(str::parse::<Strategy>) {
+            Some(Ok(strategy)) => match palette_size_for_target(strategy, nb_palettes) {
+                Ok(size) => {
+                    debug!("Targeting {nb_palettes} palettes with palettes of {size} colours");
+                    Some(size)
+                }
+                Err(err) => {
+                    error!("{err}");
+                    return ExitCode::FAILURE;
+                }
+            },
+            // This gets reported below, like without `--target-palettes`.
+            _ => None,
+        },
+    };
+    // The largest palette is the one that formats must be able to hold.
+    let palette_size = match &sizes {
+        Some(sizes) => sizes.iter().copied().max().unwrap_or(0),
+        None => target_size.or(cli.palette_size).unwrap_or(4),
+    };
+    // The strategies only get to target what's left of each palette, but formats must still hold all of it.
+    let reserved = cli.reserved_per_palette.unwrap_or(0);
+    if reserved != 0 && cli.from_spec.is_some() {
+        error!("`--reserved-per-palette` only affects strategies, not `--from-spec`");
         return ExitCode::FAILURE;
+    }
+    let smallest = match &sizes {
+        Some(sizes) => sizes.iter().copied().min().unwrap_or(0),
+        None => palette_size,
     };
+    if reserved != 0 && reserved >= smallest {
+        error!("Reserving {reserved} entries per palette leaves none in palettes of {smallest} colours");
+        return ExitCode::FAILURE;
+    }
+    let sizes: Option<Vec<usize>> =
+        sizes.map(|sizes| sizes.into_iter().map(|size| size - reserved).collect());
+    let usable_size = palette_size - reserved;
+    let tile_width = cli.tile_width.or(cli.tile_size).unwrap_or(8);
+    let tile_height = cli.tile_height.or(cli.tile_size).unwrap_or(8);
+    if tile_width == 0 || tile_height == 0 {
+        error!("Tiles must be at least 1 pixel wide and tall, not {tile_width}x{tile_height}");
+        return ExitCode::FAILURE;
+    }
+    let grid = match (cli.width_in_tiles, cli.height_in_tiles) {
+        (Some(width), Some(height)) => Some((width, height)),
+        (None, None) => None,
+        _ => {
+            error!("`--width-in-tiles` and `--height-in-tiles` must be passed together");
+            return ExitCode::FAILURE;
+        }
+    };
+    // A fixed grid takes precedence over `--columns`.
+    let columns = grid.map_or(cli.columns.unwrap_or(1), |(width, _)| width);
+    if columns == 0 {
+        error!("The image must be at least one tile wide");
+        return ExitCode::FAILURE;
+    }
+    let fixed_rows = grid.map(|(_, height)| height);
+    if fixed_rows == Some(0) {
+        error!("The image must be at least one tile tall");
+        return ExitCode::FAILURE;
+    }
 
-    gen_image(tile_size, nb_colors, tile_colors, &cli.out_path);
+    // These bypass the strategy argument, so their only positional argument is the output path.
+    let bypasses: Vec<&str> = [
+        (cli.from_spec.is_some(), "--from-spec"),
+        (sizes.is_some(), "--palette-sizes"),
+        (cli.compare.is_some(), "--compare"),
+    ]
+    .into_iter()
+    .filter_map(|(passed, flag)| passed.then_some(flag))
+    .collect();
+    let bypass = match bypasses[..] {
+        [] => None,
+        [flag] => Some(flag),
+        [first, second, ..] => {
+            error!("`{first}` and `{second}` are mutually exclusive");
+            return ExitCode::FAILURE;
+        }
+    };
+    // These generate several images, so they take no positional arguments.
+    let batch = match (cli.all_strategies, &cli.manifest) {
+        (true, Some(_)) => {
+            error!("`--all-strategies` and `--manifest` are mutually exclusive");
+            return ExitCode::FAILURE;
+        }
+        (true, None) => Some("--all-strategies"),
+        (false, Some(_)) => Some("--manifest"),
+        (false, None) => None,
+    };
+    if let Some(batch) = batch {
+        if cli.strategy.is_some() {
+            error!("`{batch}` generates several images, so no strategy can be given (nor an output path)");
+            return ExitCode::FAILURE;
+        }
+        if let Some(flag) = bypass {
+            error!("`{batch}` and `{flag}` are mutually exclusive");
+            return ExitCode::FAILURE;
+        }
+    }
+    // Only a single image can be checked or annotated like these, let alone get its metadata written to a single path.
+    if let Some(batch) = batch {
+        let single_image_flags = [
+            (cli.verify, "--verify"),
+            (cli.verify_optimal, "--verify-optimal"),
+            (cli.min_palettes.is_some(), "--min-palettes"),
+            (cli.count_only, "--count-only"),
+            (cli.explain, "--explain"),
+            (cli.stats, "--stats"),
+            (cli.assert_all_colors_used, "--assert-all-colors-used"),
+            (cli.emit_expected.is_some(), "--emit-expected"),
+            (cli.emit_spec.is_some(), "--emit-spec"),
+            (cli.emit_attrmap.is_some(), "--emit-attrmap"),
+            (cli.emit_pal.is_some(), "--emit-pal"),
+            (cli.preview_palette.is_some(), "--preview-palette"),
+            (cli.annotate, "--annotate"),
+            (cli.dedup_report, "--dedup-report"),
+            (cli.force_unique, "--force-unique"),
+        ];
+        if let Some((_, flag)) = single_image_flags.iter().find(|(passed, _)| *passed) {
+            error!("`{batch}` generates several images, so it can't be used with `{flag}`");
+            return ExitCode::FAILURE;
+        }
+    }
+    if cli.all_strategies {
+        if cli.output_dir.is_none() {
+            error!("`--all-strategies` needs an `--output-dir` to write the images to");
+            return ExitCode::FAILURE;
+        }
+    } else if cli.output_dir.is_some() {
+        error!("`--output-dir` only makes sense with `--all-strategies`");
+        return ExitCode::FAILURE;
+    }
+    let (strategy, out_path) = match bypass {
+        _ if batch.is_some() => (None, None),
+        Some(flag) if cli.out_path.is_some() => {
+            error!("No strategy can be given alongside `{flag}`");
+            return ExitCode::FAILURE;
+        }
+        // Comparing is combining, only with a different layout.
+        Some("--compare") => (Some(Strategy::Combined), cli.strategy.map(PathBuf::from)),
+        Some(_) => (None, cli.strategy.map(PathBuf::from)),
+        None => {
+            let Some(strategy) = &cli.strategy else {
+                error!("Missing strategy");
+                return ExitCode::FAILURE;
+            };
+            match strategy.parse::<Strategy>() {
+                Ok(strategy) => (Some(strategy), cli.out_path),
+                Err(err) => {
+                    error!("{err}");
+                    return ExitCode::FAILURE;
+                }
+            }
+        }
+    };
 
-    ExitCode::SUCCESS
-}
+    // Which strategies actually get run (and verified), which is only ever several for `combined`.
+    if cli.compare.is_some() && cli.combine.is_some() {
+        error!("`--compare` and `--combine` are mutually exclusive");
+        return ExitCode::FAILURE;
+    }
+    let components: Vec<Strategy> = match (strategy, cli.compare.as_ref().or(cli.combine.as_ref()))
+    {
+        (Some(Strategy::Combined), None) => Strategy::COMBINED_DEFAULT.to_vec(),
+        (Some(Strategy::Combined), Some(list)) => {
+            match list.split(',').map(|name| name.trim().parse()).collect() {
+                Ok(components) => components,
+                Err(err) => {
+                    error!("{err}");
+                    return ExitCode::FAILURE;
+                }
+            }
+        }
+        (_, Some(_)) => {
+            error!("`--combine` only makes sense with the combined strategy");
+            return ExitCode::FAILURE;
+        }
+        (strategy, None) => strategy.into_iter().collect(),
+    };
+    if components.contains(&Strategy::Combined) {
+        error!("The combined strategy can't be combined with itself");
+        return ExitCode::FAILURE;
+    }
+    let lock_indices = match &cli.lock_indices {
+        None => None,
+        Some(_) if strategy != Some(Strategy::BestFusion) => {
+            error!("`--lock-indices` only makes sense with the best_fusion strategy");
+            return ExitCode::FAILURE;
+        }
+        Some(list) => match list
+            .split(',')
+            .map(|index| index.trim().parse::<u8>())
+            .collect::<Result<Vec<_>, _>>()
+            .ok()
+            .and_then(|indices| <[u8; 2]>::try_from(indices).ok())
+        {
+            Some(indices) => Some(indices),
+            None => {
+                error!("`--lock-indices` expects two comma-separated indices, not \"{list}\"");
+                return ExitCode::FAILURE;
+            }
+        },
+    };
+    if cli.compare.is_some() && components.len() != 2 {
+        error!(
+            "`--compare` expects exactly two strategies, not {}",
+            components.len()
+        );
+        return ExitCode::FAILURE;
+    }
 
-/// Strategy: (section 3.1.2)
-///
-/// 1. Divide the colours into two palette-sized disjoint sets. (We will use even and odd indices.)
-/// 2. Make sure that each tile only has colours from either set (never both!),
-///    and that these “proto-palettes” are fed to the packing algorithm alternatingly.
-///
-/// The image can be displayed using just the two sets, but the greediness of these algorithms
-/// makes them generate N palettes composed of one “proto-palette” from each set.
-fn defeat_any_fit(palette_size: usize) -> (Vec<Vec<u8>>, u8) {
-    assert_eq!(
-        palette_size % 2,
-        0,
-        "Palette size must be even for this strategy!"
+    let pattern = match (cli.tile_fill.as_deref(), cli.density.as_deref()) {
+        (Some(_), Some(_)) => {
+            error!("`--tile-fill` and `--density` are mutually exclusive");
+            return ExitCode::FAILURE;
+        }
+        (_, Some("full")) => FillPattern::Modulo,
+        (_, Some("sparse")) => FillPattern::Sequential,
+        (_, Some(density)) => {
+            error!("Unknown density \"{density}\" (expected one of: full, sparse)");
+            return ExitCode::FAILURE;
+        }
+        (pattern, None) => match pattern.map(str::parse).transpose() {
+            Ok(pattern) => pattern.unwrap_or_default(),
+            Err(err) => {
+                error!("{err}");
+                return ExitCode::FAILURE;
+            }
+        },
+    };
+
+    let mut style = PaletteStyle::default();
+    if let Some(saturation) = cli.saturation {
+        style.saturation = saturation;
+    }
+    if let Some(hue_offset) = cli.hue_offset {
+        if !hue_offset.is_finite() {
+            error!("The hue offset must be a number of degrees, not {hue_offset}");
+            return ExitCode::FAILURE;
+        }
+        style.hue_offset = hue_offset;
+    }
+    if let Some(hue_span) = cli.hue_span {
+        // Going around more than once would make some colours identical.
+        if !(hue_span > 0.0 && hue_span <= 360.0) {
+            error!("The hue span must be above 0 and at most 360 degrees, not {hue_span}");
+            return ExitCode::FAILURE;
+        }
+        style.hue_span = hue_span;
+    }
+    if let Some(lightnesses) = &cli.light_dark {
+        match parse_light_dark(lightnesses) {
+            Some(lightness) => style.lightness = lightness,
+            None => {
+                error!("`--light-dark` expects two comma-separated numbers, not \"{lightnesses}\"");
+                return ExitCode::FAILURE;
+            }
+        }
+    }
+    if let Some(order) = &cli.color_order {
+        match order.parse() {
+            Ok(order) => style.order = order,
+            Err(err) => {
+                error!("{err}");
+                return ExitCode::FAILURE;
+            }
+        }
+    }
+    if let Some(space) = &cli.color_space {
+        match space.parse() {
+            Ok(space) => style.color_space = space,
+            Err(err) => {
+                error!("{err}");
+                return ExitCode::FAILURE;
+            }
+        }
+    }
+    if ![style.saturation, style.lightness[0], style.lightness[1]]
+        .iter()
+        .all(|value| (0.0..=1.0).contains(value))
+    {
+        error!("Saturation and lightness must be between 0 and 1");
+        return ExitCode::FAILURE;
+    }
+
+    let target: Option<Console> = match cli.target.as_deref().map(str::parse).transpose() {
+        Ok(target) => target,
+        Err(err) => {
+            error!("{err}");
+            return ExitCode::FAILURE;
+        }
+    };
+    // With `--all-strategies`, each image needs its own number of colours.
+    // Only palette files can name their colours, so the names are empty otherwise.
+    type NamedPalette = (Vec<Rgb32>, Vec<Option<String>>);
+    let alpha_index: Option<u8> = match cli.alpha_index.as_deref() {
+        None | Some("none") => None,
+        Some(index) => match index.parse() {
+            Ok(index) => Some(index),
+            Err(_) => {
+                error!("`--alpha-index` expects a colour index or `none`, not \"{index}\"");
+                return ExitCode::FAILURE;
+            }
+        },
+    };
+    let make_palette = |nb_colors: u8| -> Result<NamedPalette, String> {
+        let (mut palette, names) = match &cli.palette_file {
+            Some(path) => read_named_palette_file(path, nb_colors).map_err(|err| {
+                format!("Failed to read palette from \"{}\": {err}", path.display())
+            })?,
+            None if cli.grayscale => (build_grayscale_palette(nb_colors), vec![]),
+            None => (build_palette(nb_colors, style), vec![]),
+        };
+        if let Some(console) = target {
+            make_distinct(&mut palette, console).map_err(|err| err.to_string())?;
+        }
+        if let Some(index) = alpha_index {
+            let Some(color) = palette.get_mut(usize::from(index)) else {
+                return Err(format!(
+                    "The transparent index must be below the number of colours ({nb_colors}), not {index}"
+                ));
+            };
+            color.0 &= 0x00FF_FFFF; // Clear the alpha byte.
+        }
+        Ok((palette, names))
+    };
+
+    let format: OutputFormat = match cli.format.as_deref().map(str::parse).transpose() {
+        Ok(format) => format.unwrap_or_default(),
+        Err(err) => {
+            error!("{err}");
+            return ExitCode::FAILURE;
+        }
+    };
+    if let Some(max) = format.max_tile_colors() {
+        if palette_size > max {
+            error!(
+                "The {} format only supports palettes of up to {max} colours",
+                format.name()
+            );
+            return ExitCode::FAILURE;
+        }
+    }
+
+    if let Some(size) = format.hardware_tile_size() {
+        if !tile_width.is_multiple_of(size) || !tile_height.is_multiple_of(size) {
+            error!(
+                "The {} format is made of {size}x{size} tiles, so tiles can't be {tile_width}x{tile_height} (try `--tile-size {size}`)",
+                format.name()
+            );
+            return ExitCode::FAILURE;
+        }
+    }
+
+    let repeat = cli.repeat.unwrap_or(1);
+    if repeat == 0 {
+        error!("The tiles must be repeated at least once");
+        return ExitCode::FAILURE;
+    }
+
+    let nb_frames = cli.frames.unwrap_or(1);
+    if nb_frames == 0 {
+        error!("The image must have at least one frame");
+        return ExitCode::FAILURE;
+    }
+    if nb_frames > 1 && !format.supports_frames() {
+        error!(
+            "The {} format only supports a single frame (try `apng` or `gif`)",
+            format.name()
+        );
+        return ExitCode::FAILURE;
+    }
+
+    let pixel_order: PixelOrder = match cli.pixel_order.as_deref().map(str::parse).transpose() {
+        Ok(order) => order.unwrap_or_default(),
+        Err(err) => {
+            error!("{err}");
+            return ExitCode::FAILURE;
+        }
+    };
+    if pixel_order != PixelOrder::RowMajor && format.image_format().is_some() {
+        info!(
+            "The {} format always stores pixels row by row, so `--pixel-order column` is ignored",
+            format.name()
+        );
+    }
+
+    if cli.annotate && format.image_format().is_none() {
+        error!("Only image formats can be annotated, not {}", format.name());
+        return ExitCode::FAILURE;
+    }
+
+    if alpha_index.is_some()
+        && !matches!(
+            format,
+            OutputFormat::Png | OutputFormat::RgbPng | OutputFormat::Apng | OutputFormat::Gif
+        )
+    {
+        error!(
+            "Only PNG and GIF images can have a transparent colour, not {}",
+            format.name()
+        );
+        return ExitCode::FAILURE;
+    }
+
+    let preview_layout: PreviewLayout = match cli
+        .palette_preview_layout
+        .as_deref()
+        .map(str::parse)
+        .transpose()
+    {
+        Ok(layout) => layout.unwrap_or_default(),
+        Err(err) => {
+            error!("{err}");
+            return ExitCode::FAILURE;
+        }
+    };
+    if cli.palette_preview_layout.is_some() && cli.preview_palette.is_none() {
+        error!("`--palette-preview-layout` only makes sense with `--preview-palette`");
+        return ExitCode::FAILURE;
+    }
+    if preview_layout != PreviewLayout::Grid
+        && (cli.preview_swatch_size.is_some() || cli.preview_columns.is_some())
+    {
+        error!("`--preview-swatch-size` and `--preview-columns` only make sense with `--palette-preview-layout grid`");
+        return ExitCode::FAILURE;
+    }
+    if cli.bundle.is_some() {
+        if !cfg!(feature = "bundle") {
+            error!("`--bundle` requires no_packsaran to be built with the `bundle` feature");
+            return ExitCode::FAILURE;
+        }
+        if let Some(batch) = batch {
+            error!(
+                "`{batch}` generates several images, so they can't be put in a single `--bundle`"
+            );
+            return ExitCode::FAILURE;
+        }
+        // Otherwise, no image gets generated to go in it.
+        if out_path.is_none() || cli.count_only {
+            error!("`--bundle` requires an output path, and can't be used with `--count-only`");
+            return ExitCode::FAILURE;
+        }
+    }
+    if cli.preview_swatch_size == Some(0) || cli.preview_columns == Some(0) {
+        error!(
+            "The palette preview's swatches must be at least 1 pixel large, and at least 1 per row"
+        );
+        return ExitCode::FAILURE;
+    }
+
+    let ordering: TileOrder = match cli.ordering.as_deref().map(str::parse).transpose() {
+        Ok(ordering) => ordering.unwrap_or_default(),
+        Err(err) => {
+            error!("{err}");
+            return ExitCode::FAILURE;
+        }
+    };
+    let reorderings: Vec<&str> = [
+        (ordering != TileOrder::Crafted, "--ordering"),
+        (cli.shuffle_order, "--shuffle-order"),
+        (cli.tile_order_file.is_some(), "--tile-order-file"),
+        (cli.interleave_every.is_some(), "--interleave-every"),
+    ]
+    .into_iter()
+    .filter_map(|(passed, flag)| passed.then_some(flag))
+    .collect();
+    if let [first, second, ..] = reorderings[..] {
+        error!("`{first}` and `{second}` are mutually exclusive");
+        return ExitCode::FAILURE;
+    }
+    let shuffle_seed = match (cli.shuffle_order, cli.seed) {
+        (false, _) => None,
+        (true, Some(seed)) => Some(seed),
+        (true, None) => {
+            error!("`--shuffle-order` needs a `--seed` to shuffle with");
+            return ExitCode::FAILURE;
+        }
+    };
+    if let Some(period) = cli.interleave_every {
+        if period == 0 {
+            error!("`--interleave-every` needs runs of at least one tile");
+            return ExitCode::FAILURE;
+        }
+        if !matches!(strategy, Some(Strategy::AnyFit | Strategy::NextFit)) {
+            error!("`--interleave-every` only makes sense with the any_fit and next_fit strategies, whose tiles alternate between two sets of colours");
+            return ExitCode::FAILURE;
+        }
+    }
+    if let (Some(batch), Some(_)) = (batch, &cli.tile_order_file) {
+        error!("`{batch}` generates images with different numbers of tiles, so `--tile-order-file` can't apply to them");
+        return ExitCode::FAILURE;
+    }
+
+    if cli.compare.is_some() {
+        if format.image_format().is_none() {
+            error!(
+                "Only image formats can show strategies side by side, not {}",
+                format.name()
+            );
+            return ExitCode::FAILURE;
+        }
+        // Either would mix both strategies' tiles, so they wouldn't sit in their own halves anymore.
+        if repeat != 1 || !reorderings.is_empty() {
+            error!("`--compare` can't be used with `--repeat` nor any reordering of the tiles");
+            return ExitCode::FAILURE;
+        }
+    }
+    // How many of the tiles go in the left half of the image, if it's split.
+    let nb_left = cli
+        .compare
+        .is_some()
+        .then(|| nb_tiles(components[0], round_down_to_even(usable_size)));
+
+    let scale = cli.scale.unwrap_or(1);
+    if scale == 0 {
+        error!("The image must be scaled by at least 1");
+        return ExitCode::FAILURE;
+    }
+    if scale != 1 && format.image_format().is_none() {
+        error!("Only image formats can be scaled, not {}", format.name());
+        return ExitCode::FAILURE;
+    }
+
+    // Odd sizes are rounded down, so they must be checked after that.
+    let nb_colors = round_down_to_even(usable_size);
+    if strategy.is_some() {
+        if usable_size < 2 {
+            error!("Strategies need palettes of at least 2 usable colours, not {usable_size}");
+            return ExitCode::FAILURE;
+        }
+        for component in &components {
+            if nb_colors < component.min_palette_size() {
+                error!(
+                    "The {} strategy needs palettes of at least {} colours",
+                    component.name(),
+                    component.min_palette_size()
+                );
+                return ExitCode::FAILURE;
+            }
+        }
+    }
+    // Check this before the tiles get generated, so that the allocation fails with an explanation rather than an OOM kill.
+    let max_tiles = cli.max_tiles.unwrap_or(1 << 24);
+    let max_colors = cli.max_colors.unwrap_or(256);
+    if let Some(strategy) = strategy {
+        let expected = match cli.target_palettes {
+            // Only the pairs of tiles that fill up a palette each are kept.
+            Some(nb_palettes) if strategy == Strategy::AnyFit => {
+                nb_palettes.saturating_mul(2).saturating_mul(repeat)
+            }
+            _ => expected_nb_tiles(&components, nb_colors, repeat),
+        };
+        if expected > max_tiles {
+            error!(
+                "The {} strategy would generate {} tiles, more than the maximum of {max_tiles} (try a smaller `--palette-size`, or raising `--max-tiles`)",
+                strategy.name(),
+                fmt_saturated(expected)
+            );
+            return ExitCode::FAILURE;
+        }
+    }
+
+    let fill = TileFill {
+        pattern,
+        seed: cli.seed,
+    };
+    let max_dimension = cli.max_dimension.unwrap_or(16384);
+    let background = cli.background_index.unwrap_or(0);
+    let settings = ImageSettings {
+        tile_width,
+        tile_height,
+        columns,
+        nb_frames,
+        fill,
+        background,
+        scale,
+        format,
+    };
+
+    if let Some(dir) = &cli.output_dir {
+        if format.image_format().is_none() {
+            error!(
+                "`--all-strategies` only writes images, not {}",
+                format.name()
+            );
+            return ExitCode::FAILURE;
+        }
+        for strategy in Strategy::ALL {
+            let components = match strategy {
+                Strategy::Combined => Strategy::COMBINED_DEFAULT.to_vec(),
+                strategy => vec![strategy],
+            };
+            if let Some(component) = components
+                .iter()
+                .find(|component| nb_colors < component.min_palette_size())
+            {
+                info!(
+                    "Skipping {}, as {} needs palettes of at least {} colours",
+                    strategy.name(),
+                    component.name(),
+                    component.min_palette_size()
+                );
+                continue;
+            }
+            let expected = expected_nb_tiles(&components, nb_colors, repeat);
+            if expected > max_tiles {
+                info!(
+                    "Skipping {}, as it would generate {} tiles, more than the maximum of {max_tiles}",
+                    strategy.name(),
+                    fmt_saturated(expected)
+                );
+                continue;
+            }
+            let mut attack = match defeat(strategy, &components, nb_colors) {
+                Ok(attack) => attack,
+                Err(err) => {
+                    info!("Skipping {}: {err}", strategy.name());
+                    continue;
+                }
+            };
+            if let Err(err) = check_max_colors(&attack, max_colors) {
+                info!("Skipping {}: {err}", strategy.name());
+                continue;
+            }
+            attack.repeat(repeat);
+            match shuffle_seed {
+                Some(seed) => attack.shuffle(seed),
+                None => attack.reorder(ordering),
+            }
+
+            let nb_rows = attack.tiles.len().div_ceil(columns);
+            let nb_rows = match fixed_rows {
+                Some(fixed_rows) if fixed_rows < nb_rows => {
+                    info!(
+                        "Skipping {}, as its {} tiles don't fit in the {columns}x{fixed_rows} grid",
+                        strategy.name(),
+                        attack.tiles.len()
+                    );
+                    continue;
+                }
+                Some(fixed_rows) => fixed_rows,
+                None => nb_rows,
+            };
+            let (width, height) = (tile_width * columns * scale, tile_height * nb_rows * scale);
+            if width > max_dimension || height > max_dimension {
+                info!(
+                    "Skipping {}, as its image would be {width}x{height} pixels, more than the maximum of {max_dimension}",
+                    strategy.name()
+                );
+                continue;
+            }
+            if background >= attack.nb_colors {
+                info!(
+                    "Skipping {}, as it only uses {} colours, which the background index must be below",
+                    strategy.name(),
+                    attack.nb_colors
+                );
+                continue;
+            }
+            let palette = match make_palette(attack.nb_colors) {
+                Ok((palette, _)) => palette,
+                Err(err) => {
+                    error!("{err}");
+                    return ExitCode::FAILURE;
+                }
+            };
+
+            let path = dir.join(format!("{}.{}", strategy.name(), format.extension()));
+            if !cli.force {
+                if let Err(err) = check_not_exists(&path) {
+                    error!("{err}");
+                    return ExitCode::FAILURE;
+                }
+            }
+            let result = settings
+                .builder(&palette, attack.tiles, nb_rows)
+                .build_and_store(path.as_path());
+            match result {
+                Ok(nb_bytes_written) => {
+                    debug!("Wrote {nb_bytes_written} bytes to \"{}\"", path.display());
+                }
+                Err(err) => {
+                    error!("Failed to write image to \"{}\": {err}", path.display());
+                    return ExitCode::FAILURE;
+                }
+            }
+        }
+        return ExitCode::SUCCESS;
+    }
+
+    if let Some(manifest) = &cli.manifest {
+        let jobs = match read_manifest_file(manifest) {
+            Ok(jobs) => jobs,
+            Err(err) => {
+                error!(
+                    "Failed to read manifest from \"{}\": {err}",
+                    manifest.display()
+                );
+                return ExitCode::FAILURE;
+            }
+        };
+        if format.image_format().is_none() {
+            error!("`--manifest` only writes images, not {}", format.name());
+            return ExitCode::FAILURE;
+        }
+        let run = |job: &Job| -> Result<NonZeroUsize, String> {
+            let components = match job.strategy {
+                Strategy::Combined => Strategy::COMBINED_DEFAULT.to_vec(),
+                strategy => vec![strategy],
+            };
+            let palette_size = job.palette_size.unwrap_or(palette_size);
+            if let Some(max) = format.max_tile_colors().filter(|&max| palette_size > max) {
+                return Err(format!(
+                    "The {} format only supports palettes of up to {max} colours",
+                    format.name()
+                ));
+            }
+            if reserved >= palette_size {
+                return Err(format!("Reserving {reserved} entries per palette leaves too few in palettes of {palette_size} colours"));
+            }
+            let nb_colors = round_down_to_even(palette_size - reserved);
+            if let Some(component) = components
+                .iter()
+                .find(|component| nb_colors < component.min_palette_size())
+            {
+                return Err(format!(
+                    "The {} strategy needs palettes of at least {} colours",
+                    component.name(),
+                    component.min_palette_size()
+                ));
+            }
+            let expected = expected_nb_tiles(&components, nb_colors, repeat);
+            if expected > max_tiles {
+                return Err(format!(
+                    "It would generate {} tiles, more than the maximum of {max_tiles}",
+                    fmt_saturated(expected)
+                ));
+            }
+            let mut attack =
+                defeat(job.strategy, &components, nb_colors).map_err(|err| err.to_string())?;
+            check_max_colors(&attack, max_colors)?;
+            attack.repeat(repeat);
+            match shuffle_seed {
+                Some(seed) => attack.shuffle(seed),
+                None => attack.reorder(ordering),
+            }
+
+            let (tile_width, tile_height) = job.tile_size.unwrap_or((tile_width, tile_height));
+            if tile_width == 0 || tile_height == 0 {
+                return Err(format!(
+                    "Tiles must be at least 1 pixel wide and tall, not {tile_width}x{tile_height}"
+                ));
+            }
+            let nb_rows = attack.tiles.len().div_ceil(columns);
+            let nb_rows = match fixed_rows {
+                Some(fixed_rows) if fixed_rows < nb_rows => {
+                    return Err(format!(
+                        "Its {} tiles don't fit in the {columns}x{fixed_rows} grid",
+                        attack.tiles.len()
+                    ))
+                }
+                Some(fixed_rows) => fixed_rows,
+                None => nb_rows,
+            };
+            let (width, height) = (tile_width * columns * scale, tile_height * nb_rows * scale);
+            if width > max_dimension || height > max_dimension {
+                return Err(format!("The image would be {width}x{height} pixels, more than the maximum of {max_dimension}"));
+            }
+            if background >= attack.nb_colors {
+                return Err(format!(
+                    "The background index must be below the number of colours ({}), not {background}",
+                    attack.nb_colors
+                ));
+            }
+            let (palette, _) = make_palette(attack.nb_colors)?;
+            if !cli.force {
+                check_not_exists(&job.out_path)?;
+            }
+            ImageSettings {
+                tile_width,
+                tile_height,
+                ..settings
+            }
+            .builder(&palette, attack.tiles, nb_rows)
+            .build_and_store(job.out_path.as_path())
+            .map_err(|err| format!("Failed to write image: {err}"))
+        };
+
+        // Only report once everything has been attempted, so that failures don't get lost among the progress lines.
+        let results: Vec<_> = jobs.iter().map(run).collect();
+        let mut nb_failed = 0;
+        for (i, (job, result)) in jobs.iter().zip(&results).enumerate() {
+            match result {
+                Ok(nb_bytes_written) => println!(
+                    "Job #{i} ({}): wrote {nb_bytes_written} bytes to \"{}\"",
+                    job.strategy.name(),
+                    job.out_path.display()
+                ),
+                Err(err) => {
+                    nb_failed += 1;
+                    println!(
+                        "Job #{i} ({}): FAILED to generate \"{}\": {err}",
+                        job.strategy.name(),
+                        job.out_path.display()
+                    );
+                }
+            }
+        }
+        if nb_failed != 0 {
+            error!("{nb_failed} of {} jobs failed", jobs.len());
+            return ExitCode::FAILURE;
+        }
+        return ExitCode::SUCCESS;
+    }
+    if out_path.is_none() && !cli.count_only && !cli.verify && !cli.verify_optimal && !cli.explain {
+        error!("Missing output path");
+        return ExitCode::FAILURE;
+    }
+
+    let mut attack = match strategy {
+        None => match &sizes {
+            Some(sizes) => match defeat_mixed(sizes) {
+                Ok(attack) => attack,
+                Err(err) => {
+                    error!("{err}");
+                    return ExitCode::FAILURE;
+                }
+            },
+            None => {
+                let path = cli.from_spec.as_deref().unwrap(); // Only `--from-spec` can otherwise omit the strategy.
+                match read_spec_file(path) {
+                    Ok(attack) => attack,
+                    Err(err) => {
+                        error!("Failed to read spec from \"{}\": {err}", path.display());
+                        return ExitCode::FAILURE;
+                    }
+                }
+            }
+        },
+        Some(strategy) => {
+            let attack = match lock_indices {
+                Some(indices) => {
+                    defeat_best_fusion_locked_at(nb_colors, indices).map_err(|err| err.to_string())
+                }
+                None => match cli.target_palettes {
+                    Some(nb_palettes) => {
+                        defeat_targeting(strategy, nb_palettes).map_err(|err| err.to_string())
+                    }
+                    None => defeat(strategy, &components, nb_colors).map_err(|err| err.to_string()),
+                },
+            };
+            match attack {
+                Ok(attack) => attack,
+                Err(err) => {
+                    error!("{err}");
+                    return ExitCode::FAILURE;
+                }
+            }
+        }
+    };
+    attack.repeat(repeat);
+    // Keep the tiles in the order that the strategy crafted them in, so that `--verify` can compare.
+    let crafted_tiles = match shuffle_seed {
+        None if reorderings.is_empty() => None,
+        None => {
+            let crafted = attack.tiles.clone();
+            match &cli.tile_order_file {
+                Some(path) => match read_tile_order_file(path, attack.tiles.len()) {
+                    Ok(order) => attack.permute(&order),
+                    Err(err) => {
+                        error!(
+                            "Failed to read tile order from \"{}\": {err}",
+                            path.display()
+                        );
+                        return ExitCode::FAILURE;
+                    }
+                },
+                None => match cli.interleave_every {
+                    Some(period) => attack.interleave_every(period),
+                    None => attack.reorder(ordering),
+                },
+            }
+            Some(crafted)
+        }
+        Some(seed) => {
+            let crafted = attack.tiles.clone();
+            attack.shuffle(seed);
+            Some(crafted)
+        }
+    };
+    debug!(
+        "Generated {} tiles, using {} colours and needing {} palettes",
+        attack.tiles.len(),
+        attack.nb_colors,
+        attack.nb_optimal_palettes()
     );
-    let nb_colors_per_tile = palette_size / 2;
-    let nb_colors =
-        u8::try_from(palette_size * 2).expect("Only color indices up to 256 are supported!");
+    if let Err(err) = check_max_colors(&attack, max_colors) {
+        error!("{err} (try a smaller `--palette-size`, or raising `--max-colors`)");
+        return ExitCode::FAILURE;
+    }
 
-    (
-        combination::combine::index(palette_size, nb_colors_per_tile)
-            .into_iter()
-            .flat_map(|combination| {
-                let (evens, odds) = combination
-                    .into_iter()
-                    .map(|index| {
-                        let even = (index * 2) as u8;
-                        (even, even + 1)
+    if cli.force_unique {
+        let nb_left = attack.make_unique(fill, tile_width, tile_height);
+        if nb_left != 0 {
+            info!("Warning: {nb_left} tiles are still duplicates, as they can't be rearranged (e.g. solid ones)");
+        }
+    }
+    if cli.dedup_report {
+        println!(
+            "Duplicate tiles: {}",
+            attack.nb_duplicate_tiles(fill, tile_width, tile_height)
+        );
+    }
+
+    if cli.explain {
+        match strategy {
+            Some(strategy) => {
+                println!(
+                    "Strategy: {} ({nb_colors} colours per palette)",
+                    strategy.name()
+                );
+                println!("{}", strategy.construction());
+                if strategy == Strategy::Combined {
+                    for component in &components {
+                        println!("\n{}:\n{}", component.name(), component.construction());
+                    }
+                }
+            }
+            None => match &sizes {
+                Some(sizes) => println!("Palette sizes: {sizes:?}"),
+                None => println!("Tiles read from a spec"),
+            },
+        }
+        println!("\nProto-palettes:");
+        for (i, tile) in attack.tiles.iter().enumerate() {
+            print!("#{i}:");
+            for color in tile.colors() {
+                print!(" {color}");
+            }
+            println!();
+        }
+        println!("\nOptimal palettes: {}", attack.nb_optimal_palettes());
+        match (strategy, &sizes) {
+            (Some(_), _) => {
+                for &component in &components {
+                    println!(
+                        "Expected {} palettes: {}",
+                        component.name(),
+                        fmt_saturated(worst_case_palettes(component, nb_colors))
+                    );
+                }
+            }
+            (None, Some(sizes)) => println!("Expected first_fit palettes: {}", sizes.len() + 1),
+            (None, None) => {}
+        }
+    }
+
+    if cli.assert_all_colors_used {
+        let unused = attack.unused_colors();
+        if !unused.is_empty() {
+            let list: Vec<_> = unused.iter().map(u8::to_string).collect();
+            let list = list.join(", ");
+            error!(
+                "{} of the {} colours are never used: {list}",
+                unused.len(),
+                attack.nb_colors
+            );
+            return ExitCode::from(VERIFICATION_FAILED);
+        }
+    }
+
+    if cli.verify {
+        let nb_optimal = attack.nb_optimal_palettes();
+        // The combined strategy has no algorithm of its own, so each of the ones it combines must be defeated instead.
+        let pack = |tiles: &[ProtoPalette]| -> Vec<(&str, usize)> {
+            match (strategy, &sizes) {
+                (None, Some(sizes)) => vec![("first_fit", pack_mixed(tiles, sizes).len())],
+                (None, None) => vec![],
+                (Some(_), _) => components
+                    .iter()
+                    .map(|&component| {
+                        let nb_greedy = pack_greedy(component, tiles, nb_colors).len();
+                        (component.name(), nb_greedy)
                     })
-                    .collect();
-                [evens, odds]
-            })
-            .collect(),
-        nb_colors,
-    )
-}
-
-/// Strategy: (section 3.2.2)
-///
-/// 0. Let `N = palette_size`, for conciseness, and `A = 0..N` the input alphabet.
-/// 1. Construct N tiles of (N-1) colours each. (= the (N-1)-combinations of A).
-/// 2. For each (non-overlapping) pair of those tiles, take their intersection (should have size N-2), add two “locking” colours (N and N+1), and do the same as step 1.
-/// 3. Make sure each tile uses one proto-palette from step 1, then one from step 2 (never using both A and B), etc.
-/// 4. If N > 2, emit the remaining tiles that take
-///
-/// The image can be displayed using one palette containing all of A, and one palette containing each of the generated `intersection`s with N and N+1 added.
-fn defeat_best_fusion(palette_size: usize) -> (Vec<Vec<u8>>, u8) {
-    assert_eq!(
-        palette_size % 2,
-        0,
-        "Palette size must be even for this strategy!"
-    );
-    let nb_colors =
-        u8::try_from(palette_size + 2).expect("Only color indices up to 256 are supported!");
+                    .collect(),
+            }
+        };
+        // How the tiles were reordered, if they were.
+        let reordered = if cli.shuffle_order {
+            "shuffled".to_owned()
+        } else if cli.tile_order_file.is_some() {
+            "from file".to_owned()
+        } else if let Some(period) = cli.interleave_every {
+            format!("interleaved every {period}")
+        } else {
+            ordering.name().to_owned()
+        };
+        let print = |results: &[(&str, usize)], reordered: Option<&str>| {
+            for &(name, nb_greedy) in results {
+                match (strategy == Some(Strategy::Combined), reordered) {
+                    (false, None) => println!("Greedy palettes: {nb_greedy}"),
+                    (false, Some(how)) => println!("Greedy palettes ({how}): {nb_greedy}"),
+                    (true, None) => println!("Greedy palettes ({name}): {nb_greedy}"),
+                    (true, Some(how)) => println!("Greedy palettes ({name}, {how}): {nb_greedy}"),
+                }
+            }
+        };
 
-    let t0 = combination::combine::index(palette_size, palette_size - 1); // Note that `t0.len() == palette_size`.
-    debug_assert_eq!(t0.len(), palette_size); // Guaranteed by maths.
-    let a = nb_colors - 2;
-    let b = nb_colors - 1;
+        let results = pack(crafted_tiles.as_deref().unwrap_or(&attack.tiles));
+        if results.is_empty() {
+            error!("`--verify` needs a strategy to verify against");
+            return ExitCode::FAILURE;
+        }
+        print(&results, None);
+        // This is only informative: the strategies are meant to be defeated by their crafted order.
+        if crafted_tiles.is_some() {
+            print(&pack(&attack.tiles), Some(&reordered));
+        }
+        println!("Optimal palettes: {nb_optimal}");
+        for (name, nb_greedy) in results {
+            if nb_greedy <= nb_optimal {
+                error!("{name} was not defeated!");
+                return ExitCode::from(VERIFICATION_FAILED);
+            }
+            if let Some(min_palettes) = cli.min_palettes {
+                if nb_greedy < min_palettes {
+                    error!(
+                        "{name} needed {nb_greedy} palettes, but at least {min_palettes} were required"
+                    );
+                    return ExitCode::from(VERIFICATION_FAILED);
+                }
+            }
+        }
+    } else if cli.min_palettes.is_some() {
+        error!("`--min-palettes` only makes sense with `--verify`");
+        return ExitCode::FAILURE;
+    }
 
-    let mut proto_palettes =
-        Vec::with_capacity(palette_size * 2 + (palette_size / 2) * (palette_size - 2));
-    for i in 0..palette_size / 2 {
-        let first = &t0[i * 2];
-        let second = &t0[i * 2 + 1];
+    if cli.verify_optimal {
+        if sizes.is_some() {
+            error!(
+                "`--verify-optimal` only supports palettes of a single size, not `--palette-sizes`"
+            );
+            return ExitCode::FAILURE;
+        }
+        let nb_claimed = attack.nb_optimal_palettes();
+        if nb_claimed == 0 {
+            error!("There are no optimal palettes to check against");
+            return ExitCode::FAILURE;
+        }
+        let nb_exact = match pack_optimal(&attack.tiles, nb_colors) {
+            Ok(palettes) => palettes.len(),
+            Err(err) => {
+                error!("{err}");
+                return ExitCode::FAILURE;
+            }
+        };
+        println!("Exact palettes: {nb_exact}");
+        if nb_exact < nb_claimed {
+            error!("The tiles fit in {nb_exact} palettes, fewer than the {nb_claimed} claimed to be optimal!");
+            return ExitCode::from(VERIFICATION_FAILED);
+        }
+        if nb_exact > nb_claimed {
+            error!("The tiles need {nb_exact} palettes, so the {nb_claimed} claimed to be optimal can't be valid!");
+            return ExitCode::from(VERIFICATION_FAILED);
+        }
+    }
 
-        let intersection: Vec<_> = first
+    if cli.count_only {
+        let mut proto_palettes: Vec<_> = attack
+            .tiles
             .iter()
-            .filter_map(|t| second.contains(t).then_some(*t as u8))
+            .map(|tile| {
+                let mut colors = tile.colors().to_vec();
+                colors.sort_unstable();
+                colors
+            })
             .collect();
-        debug_assert_eq!(intersection.len(), palette_size - 2); // We will add 1 extra to end up at `palette_size - 1`.
+        proto_palettes.sort_unstable();
+        proto_palettes.dedup();
+
+        println!("Tiles: {}", attack.tiles.len());
+        println!("Distinct proto-palettes: {}", proto_palettes.len());
+        println!("Colours: {}", attack.nb_colors);
+        return ExitCode::SUCCESS;
+    }
+    let Some(out_path) = out_path else {
+        // Only verifying was requested.
+        return ExitCode::SUCCESS;
+    };
 
-        fn u8ify(slice: &[usize]) -> Vec<u8> {
-            slice.iter().map(|&idx| idx as u8).collect()
+    let (nb_columns, nb_rows) = match nb_left {
+        Some(nb_left) => (
+            columns * 2 + 1,
+            nb_left
+                .div_ceil(columns)
+                .max(attack.tiles.len().saturating_sub(nb_left).div_ceil(columns)),
+        ),
+        None => (columns, attack.tiles.len().div_ceil(columns)),
+    };
+    let nb_rows = match fixed_rows {
+        Some(fixed_rows) if fixed_rows < nb_rows => {
+            error!(
+                "The {} tiles don't fit in the {columns}x{fixed_rows} grid (they need at least {nb_rows} rows)",
+                attack.tiles.len()
+            );
+            return ExitCode::FAILURE;
         }
-        let lock = |lock_color| {
-            let mut locked = intersection.clone();
-            locked.push(lock_color);
-            locked
-        };
-        // These two will go into one palette, and fill it up.
-        proto_palettes.push(u8ify(first));
-        proto_palettes.push(lock(a));
-        // These two will go into another one, and fill it up.
-        proto_palettes.push(u8ify(second));
-        proto_palettes.push(lock(b));
-
-        // Normally this would be done in a separate loop after this one complete, but honestly we can do both at the same time, and it saves a bit of computation.
-        let rest = combination::combine::from_vec_at(&intersection, palette_size - 3); // We will add 2 extra to end up at `palette_size - 1`.
-        debug_assert_eq!(rest.len(), palette_size - 2); // Guaranteed by maths.
-
-        // This entire `extend` will only generate a single extra palette.
-        proto_palettes.extend(rest.into_iter().map(|mut subpal| {
-            subpal.push(a);
-            subpal.push(b);
-            subpal
-        }));
-    }
-
-    debug_assert_eq!(
-        proto_palettes.len(),
-        palette_size * 2 + (palette_size / 2) * (palette_size - 2)
+        Some(fixed_rows) => fixed_rows,
+        None => nb_rows,
+    };
+    let (width, height) = (
+        tile_width * nb_columns * scale,
+        tile_height * nb_rows * scale,
     );
-    (proto_palettes, nb_colors)
-}
-
-/// Generates tiles that contain the specified colours and none else.
-fn gen_image(tile_size: usize, nb_colors: u8, tile_colors: Vec<Vec<u8>>, path: &Path) {
-    let palette = (0..nb_colors).map(|i| {
-        let (red, green, blue) = Srgb::from_color(Hsl::new_srgb(
-            f32::from(i / 2) / f32::from(nb_colors / 2) * 360.,
-            1.0,                                  // Max saturation.
-            if i % 2 == 0 { 0.25 } else { 0.75 }, // Alternate between darker and brighter colours.
-        ))
-        .into_format()
-        .into_components();
-        Rgb32(u32::from_le_bytes([red, green, blue, 0xFF]))
-    });
+    // Check this before the image gets allocated, as that would fail (or, worse, succeed) otherwise.
+    if format.image_format().is_some() && (width > max_dimension || height > max_dimension) {
+        error!(
+            "The image would be {width}x{height} pixels, more than the maximum of {max_dimension} (try reshaping it with `--columns`, or raising `--max-dimension`)"
+        );
+        return ExitCode::FAILURE;
+    }
+    // `-` is the usual convention for "standard output".
+    let to_stdout = out_path.as_os_str() == "-";
+    if !cli.force {
+        let out_path = Some(&out_path).filter(|_| !to_stdout);
+        for path in out_path.into_iter().chain(&cli.bundle) {
+            if let Err(err) = check_not_exists(path) {
+                error!("{err}");
+                return ExitCode::FAILURE;
+            }
+        }
+    }
+
+    if background >= attack.nb_colors {
+        error!(
+            "The background index must be below the number of colours ({}), not {background}",
+            attack.nb_colors
+        );
+        return ExitCode::FAILURE;
+    }
+
+    if let Some(path) = &cli.emit_expected {
+        if let Err(err) = write_palettes_json(&attack.optimal_palettes, path) {
+            error!(
+                "Failed to write expected palettes to \"{}\": {err}",
+                path.display()
+            );
+            return ExitCode::FAILURE;
+        }
+    }
+
+    if let Some(path) = &cli.emit_attrmap {
+        let written = encode_gbc_attrmap(&attack)
+            .map(|attrmap| std::fs::write(path, &attrmap).map(|()| attrmap.len()));
+        match written {
+            Ok(Ok(nb_bytes_written)) => {
+                debug!("Wrote {nb_bytes_written} bytes to \"{}\"", path.display());
+            }
+            Ok(Err(err)) => {
+                error!(
+                    "Failed to write attribute map to \"{}\": {err}",
+                    path.display()
+                );
+                return ExitCode::FAILURE;
+            }
+            Err(err) => {
+                error!("{err}");
+                return ExitCode::FAILURE;
+            }
+        }
+    }
+
+    let (mut palette, color_names) = match make_palette(attack.nb_colors) {
+        Ok(palette) => palette,
+        Err(err) => {
+            error!("{err}");
+            return ExitCode::FAILURE;
+        }
+    };
+
+    let spec = Spec {
+        strategy,
+        // Mixed sizes aren't rounded down, and only the largest one can be recorded.
+        palette_size: if sizes.is_some() {
+            usable_size
+        } else {
+            nb_colors
+        },
+        tile_width,
+        tile_height,
+        attack: &attack,
+        color_names: &color_names,
+    };
+    if let Some(path) = &cli.emit_spec {
+        if let Err(err) = write_spec_json(&spec, path) {
+            error!("Failed to write spec to \"{}\": {err}", path.display());
+            return ExitCode::FAILURE;
+        }
+    }
+    // The tiles get consumed when writing the image, so these must be formatted beforehand.
+    let bundled_json = cli
+        .bundle
+        .is_some()
+        .then(|| (spec_json(&spec), palettes_json(&attack.optimal_palettes)));
 
-    let mut img = PalettedImage32::new_zeroed(
-        ImageFormat::Png,
-        AlphaMode::ZeroIsTransparent,
-        1,
-        tile_size,
-        tile_size * tile_colors.len(),
-        palette,
-    )
-    .unwrap();
-    let mut frame = img.frame_mut(0);
-    for (i, tile) in tile_colors.iter().enumerate() {
-        for y in 0..tile_size {
-            let dest_y = i * tile_size + y;
-            for x in 0..tile_size {
-                frame[(x, dest_y)] = tile[(x + y * tile_size) % tile.len()];
+    if let Some(path) = &cli.emit_pal {
+        let pal = if cli.emit_attrmap.is_some() {
+            encode_gbc_palettes(&attack, &palette)
+        } else {
+            encode_bgr555_palette(&palette)
+        };
+        let written = pal.map(|pal| std::fs::write(path, &pal).map(|()| pal.len()));
+        match written {
+            Ok(Ok(nb_bytes_written)) => {
+                debug!("Wrote {nb_bytes_written} bytes to \"{}\"", path.display());
+            }
+            Ok(Err(err)) => {
+                error!("Failed to write palette to \"{}\": {err}", path.display());
+                return ExitCode::FAILURE;
+            }
+            Err(err) => {
+                error!("{err}");
+                return ExitCode::FAILURE;
             }
         }
     }
 
-    match img.store(path) {
-        Ok(_nb_bytes_written) => {} // OK
+    let (swatch_size, preview_columns) = (
+        cli.preview_swatch_size.unwrap_or(8),
+        cli.preview_columns.unwrap_or(16),
+    );
+    if let Some(path) = &cli.preview_palette {
+        let result = store_palette_preview(
+            &palette,
+            preview_layout,
+            swatch_size,
+            preview_columns,
+            path.as_path(),
+        );
+        match result {
+            Ok(nb_bytes_written) => {
+                debug!("Wrote {nb_bytes_written} bytes to \"{}\"", path.display());
+            }
+            Err(err) => {
+                error!(
+                    "Failed to write palette preview to \"{}\": {err}",
+                    path.display()
+                );
+                return ExitCode::FAILURE;
+            }
+        }
+    }
+
+    let bundled_preview = match cli.bundle {
+        Some(_) => {
+            let mut preview = vec![];
+            let result = store_palette_preview(
+                &palette,
+                preview_layout,
+                swatch_size,
+                preview_columns,
+                Output(&mut preview),
+            );
+            if let Err(err) = result {
+                error!("Failed to generate palette preview: {err}");
+                return ExitCode::FAILURE;
+            }
+            Some(preview)
+        }
+        None => None,
+    };
+
+    // Added after the preview, as it isn't one of the tiles' colours.
+    let label = cli.annotate.then(|| {
+        palette.push(Rgb32(0xFFFF_FFFF)); // Opaque white.
+        (palette.len() - 1) as u8
+    });
+
+    // The tiles may be consumed when writing the image, so take note of these beforehand.
+    let nb_tiles = attack.tiles.len();
+    let largest_proto_palette = attack.tiles.iter().map(ProtoPalette::len).max();
+    let nb_optimal = attack.nb_optimal_palettes();
+
+    // The raw formats don't check this themselves (unlike images), and empty tiles have no pixels to encode.
+    if let Some(i) = attack.tiles.iter().position(ProtoPalette::is_empty) {
+        error!("Tile #{i} has no colours");
+        return ExitCode::FAILURE;
+    }
+    let encoded = match format {
+        OutputFormat::Png
+        | OutputFormat::RgbPng
+        | OutputFormat::Apng
+        | OutputFormat::Gif
+        | OutputFormat::Bmp => None,
+        OutputFormat::OneBpp => Some(encode_1bpp(
+            tile_width,
+            tile_height,
+            &attack.tiles,
+            fill,
+            pixel_order,
+        )),
+        // The attributes select optimal palettes, so the pixels must index into those.
+        OutputFormat::TwoBpp if cli.emit_attrmap.is_some() => Some(encode_gbc_2bpp(
+            tile_width,
+            tile_height,
+            &attack,
+            fill,
+            pixel_order,
+        )),
+        OutputFormat::TwoBpp => Some(encode_2bpp(
+            tile_width,
+            tile_height,
+            &attack.tiles,
+            fill,
+            pixel_order,
+        )),
+        OutputFormat::FourBpp => Some(encode_4bpp(
+            tile_width,
+            tile_height,
+            &attack.tiles,
+            fill,
+            pixel_order,
+        )),
+        OutputFormat::RgbdsAsm => Some(encode_rgbds_asm(
+            tile_width,
+            tile_height,
+            &attack.tiles,
+            fill,
+            pixel_order,
+        )),
+        OutputFormat::CHeader => Some(Ok(encode_c_header(
+            tile_width,
+            tile_height,
+            attack.nb_colors,
+            &attack.tiles,
+            fill,
+            pixel_order,
+            cli.array_name.as_deref().unwrap_or("evil_tiles"),
+        ))),
+    };
+    // The image's bytes, if they are to be bundled too.
+    let mut bundled_image = None;
+    let result = match encoded {
+        None => {
+            let builder = settings.builder(&palette, attack.tiles, nb_rows);
+            let builder = match nb_left {
+                Some(nb_left) => builder.side_by_side(nb_left),
+                None => builder,
+            };
+            let builder = match label {
+                Some(label) => builder.annotate(label),
+                None => builder,
+            };
+            if cli.bundle.is_some() {
+                let mut image = vec![];
+                let result = builder
+                    .build_and_store(Output(&mut image))
+                    .and_then(|_| write_output(&out_path, to_stdout, &image));
+                bundled_image = Some(image);
+                result
+            } else if to_stdout {
+                builder
+                    .build_and_store(Output(std::io::stdout().lock()))
+                    .map(NonZeroUsize::get)
+            } else {
+                builder
+                    .build_and_store(out_path.as_path())
+                    .map(NonZeroUsize::get)
+            }
+        }
+        Some(Ok(data)) => {
+            let result = write_output(&out_path, to_stdout, &data);
+            bundled_image = Some(data);
+            result
+        }
+        Some(Err(err)) => {
+            error!("{err}");
+            return ExitCode::FAILURE;
+        }
+    };
+    match result {
+        Ok(nb_bytes_written) => {
+            if to_stdout {
+                debug!("Wrote {nb_bytes_written} bytes to standard output");
+            } else {
+                debug!(
+                    "Wrote {nb_bytes_written} bytes to \"{}\"",
+                    out_path.display()
+                );
+            }
+            if cli.stats {
+                info!("Tiles: {nb_tiles}");
+                info!("Colours: {}", attack.nb_colors);
+                info!("Palette size: {nb_colors}");
+                info!(
+                    "Largest proto-palette: {}",
+                    largest_proto_palette.unwrap_or(0)
+                );
+                info!("Image size: {width}x{height} pixels");
+                info!("Optimal palettes: {nb_optimal}");
+                info!("Bytes written: {nb_bytes_written}");
+            }
+            if let (Some(path), Some(image), Some((spec, expected)), Some(preview)) =
+                (&cli.bundle, bundled_image, bundled_json, bundled_preview)
+            {
+                let image_name = format!("image.{}", format.extension());
+                let files = [
+                    (image_name.as_str(), image.as_slice()),
+                    ("spec.json", spec.as_bytes()),
+                    ("expected.json", expected.as_bytes()),
+                    ("palette.png", preview.as_slice()),
+                ];
+                if let Err(err) = write_bundle(path, &files) {
+                    error!("Failed to write bundle to \"{}\": {err}", path.display());
+                    return ExitCode::FAILURE;
+                }
+                debug!("Bundled {} files into \"{}\"", files.len(), path.display());
+            }
+            ExitCode::SUCCESS
+        }
         Err(err) => {
-            eprintln!("Failed to write image to \"{}\": {err}", path.display());
-            std::process::exit(1);
+            error!("Failed to write image to \"{}\": {err}", out_path.display());
+            ExitCode::FAILURE
         }
     }
 }
+
+/// The options that every image is generated with, whichever mode generates it.
+#[derive(Debug, Clone, Copy)]
+struct ImageSettings {
+    tile_width: usize,
+    tile_height: usize,
+    columns: usize,
+    nb_frames: usize,
+    fill: TileFill,
+    background: u8,
+    scale: usize,
+    format: OutputFormat,
+}
+
+impl ImageSettings {
+    /// Sets up the generation of an image of the `tiles`, `nb_rows` tall; only image formats may get this far.
+    fn builder(
+        self,
+        palette: &[Rgb32],
+        tiles: Vec<ProtoPalette>,
+        nb_rows: usize,
+    ) -> ImageBuilder<'_, Vec<ProtoPalette>> {
+        ImageBuilder::new()
+            .tile_size(self.tile_width, self.tile_height)
+            .columns(self.columns)
+            .rows(nb_rows)
+            .frames(self.nb_frames)
+            .palette(palette)
+            .tiles(tiles)
+            .fill(self.fill)
+            .background(self.background)
+            .scale(self.scale)
+            .progress(verbosity_is_at_least(Verbosity::Normal))
+            .truecolor(self.format == OutputFormat::RgbPng)
+            .format(self.format.image_format().unwrap_or(ImageFormat::Png))
+    }
+}
+
+/// Writes `data` to `out_path`, or to standard output; returning how many bytes were written.
+fn write_output(out_path: &Path, to_stdout: bool, data: &[u8]) -> std::io::Result<usize> {
+    if to_stdout {
+        std::io::stdout().lock().write_all(data)?;
+    } else {
+        std::fs::write(out_path, data)?;
+    }
+    Ok(data.len())
+}
+
+/// Writes the palette preview to `dest`, in the chosen layout; returning how many bytes were written.
+fn store_palette_preview<Dest: ImageDest>(
+    palette: &[Rgb32],
+    layout: PreviewLayout,
+    swatch_size: usize,
+    columns: usize,
+    dest: Dest,
+) -> std::io::Result<NonZeroUsize> {
+    match layout {
+        PreviewLayout::Strip => gen_palette_preview(palette, dest),
+        PreviewLayout::Grid => gen_palette_grid(palette, swatch_size, columns, dest),
+    }
+}
+
+#[cfg(feature = "bundle")]
+use no_packsaran::write_bundle;
+
+/// Without the `bundle` feature, `--bundle` is rejected before anything gets generated.
+#[cfg(not(feature = "bundle"))]
+fn write_bundle(_path: &Path, _files: &[(&str, &[u8])]) -> std::io::Result<()> {
+    unreachable!("`--bundle` requires the `bundle` feature")
+}
+
+/// Parses `--light-dark`'s "dark,bright" argument.
+fn parse_light_dark(arg: &str) -> Option<[f32; 2]> {
+    let (dark, bright) = arg.split_once(',')?;
+    Some([dark.trim().parse().ok()?, bright.trim().parse().ok()?])
+}
+
+fn parse_palette_sizes(arg: &str) -> Option<Vec<usize>> {
+    arg.split(',')
+        .map(|size| size.trim().parse().ok())
+        .collect()
+}
+
+/// Refuses to overwrite `path` (unless `--force` is passed, in which case this shouldn't be called).
+fn check_not_exists(path: &Path) -> Result<(), String> {
+    match path.try_exists() {
+        Ok(false) => Ok(()),
+        Ok(true) => Err(format!(
+            "\"{}\" already exists (pass `--force` to overwrite it)",
+            path.display()
+        )),
+        Err(err) => Err(format!(
+            "Failed to check whether \"{}\" exists: {err}",
+            path.display()
+        )),
+    }
+}
+
+/// Refuses tiles that use more than `--max-colors` colours, which the target hardware couldn't display all of.
+fn check_max_colors(attack: &Attack, max_colors: usize) -> Result<(), String> {
+    let nb_colors = usize::from(attack.nb_colors);
+    if nb_colors > max_colors {
+        return Err(format!(
+            "The tiles use {nb_colors} colours (across {} palettes), more than the maximum of {max_colors}",
+            attack.nb_optimal_palettes()
+        ));
+    }
+    Ok(())
+}
+
+/// How many tiles running the `components` would generate, `repeat`ed.
+fn expected_nb_tiles(components: &[Strategy], palette_size: usize, repeat: usize) -> usize {
+    components
+        .iter()
+        .map(|&component| nb_tiles(component, palette_size))
+        .fold(0, usize::saturating_add)
+        .saturating_mul(repeat)
+}
+
+/// Counts that saturated are only known to be at least that large.
+fn fmt_saturated(count: usize) -> String {
+    if count == usize::MAX {
+        format!("at least {count}")
+    } else {
+        count.to_string()
+    }
+}
+
+/// Runs the strategy; or for the combined one, the `components`.
+fn defeat(
+    strategy: Strategy,
+    components: &[Strategy],
+    palette_size: usize,
+) -> Result<Attack, CombineError> {
+    match strategy {
+        Strategy::Combined => defeat_combined(components, palette_size),
+        _ => defeat_single(strategy, palette_size),
+    }
+}
@@ -0,0 +1,158 @@
+/*
+    no_packsaran — Generator of images to defeat
+    Copyright (C) 2024  Eldred “ISSOtm” Habert <agpl@eldred.fr>
+
+    This program is free software: you can redistribute it and/or modify
+    it under the terms of the GNU Affero General Public License as published by
+    the Free Software Foundation, either version 3 of the License, or
+    (at your option) any later version.
+
+    This program is distributed in the hope that it will be useful,
+    but WITHOUT ANY WARRANTY; without even the implied warranty of
+    MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+    GNU Affero General Public License for more details.
+
+    You should have received a copy of the GNU Affero General Public License
+    along with this program.  If not, see <https://www.gnu.org/licenses/>.
+*/
+
+//! Lists of images to generate in a single run ("manifests"), as JSON.
+
+use std::{
+    fmt::Display,
+    path::{Path, PathBuf},
+};
+
+use crate::{
+    spec::{Json, Parser},
+    SpecFileError, Strategy, UnknownStrategy,
+};
+
+/// One of the images that a manifest asks for.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Job {
+    pub strategy: Strategy,
+    /// How many colours each palette can hold, if the job says.
+    pub palette_size: Option<usize>,
+    /// How wide and tall the tiles are, if the job says.
+    pub tile_size: Option<(usize, usize)>,
+    /// Where to write the image; relative paths are relative to the manifest's directory.
+    pub out_path: PathBuf,
+}
+
+/// Reads a JSON array of jobs, e.g. `[{ "strategy": "any_fit", "palette_size": 4, "out_path": "any_fit.png" }]`.
+///
+/// Each job requires a `strategy` and an `out_path`; `palette_size` and `tile_size` are optional, so that each caller
+/// can pick its own defaults. Like in specs, `tile_size` can also be a `[width, height]` pair. Any other fields are ignored.
+pub fn read_manifest_file(path: &Path) -> Result<Vec<Job>, ManifestError> {
+    let contents = std::fs::read_to_string(path).map_err(ManifestError::Io)?;
+    let manifest = Parser::parse(&contents).map_err(|err| match err {
+        SpecFileError::Syntax(line_no) => ManifestError::Syntax(line_no),
+        _ => unreachable!("Parsing can only fail with syntax errors"),
+    })?;
+    let Json::Array(jobs) = manifest else {
+        return Err(ManifestError::NotAList);
+    };
+    let dir = path.parent().unwrap_or(Path::new(""));
+
+    jobs.iter()
+        .enumerate()
+        .map(|(job, fields)| {
+            let Json::Object(fields) = fields else {
+                return Err(ManifestError::BadField {
+                    job,
+                    field: "(job)",
+                });
+            };
+            let field = |name| fields.iter().find(|(key, _)| key == name).map(|(_, v)| v);
+            let bad_field = |field| ManifestError::BadField { job, field };
+            let integer = |name| match field(name) {
+                None => Ok(None),
+                Some(value) => value
+                    .as_integer()
+                    .and_then(|n| usize::try_from(n).ok())
+                    .map(Some)
+                    .ok_or(bad_field(name)),
+            };
+
+            let strategy = match field("strategy") {
+                Some(Json::String(name)) => name
+                    .parse()
+                    .map_err(|err| ManifestError::UnknownStrategy { job, err })?,
+                Some(_) => return Err(bad_field("strategy")),
+                None => {
+                    return Err(ManifestError::MissingField {
+                        job,
+                        field: "strategy",
+                    })
+                }
+            };
+            let palette_size = integer("palette_size")?;
+            let tile_size = match field("tile_size") {
+                Some(Json::Array(sides)) => match sides[..] {
+                    [ref width, ref height] => width
+                        .as_integer()
+                        .zip(height.as_integer())
+                        .and_then(|(width, height)| {
+                            Some((usize::try_from(width).ok()?, usize::try_from(height).ok()?))
+                        })
+                        .map(Some)
+                        .ok_or(bad_field("tile_size"))?,
+                    _ => return Err(bad_field("tile_size")),
+                },
+                _ => integer("tile_size")?.map(|size| (size, size)),
+            };
+            let out_path = match field("out_path") {
+                Some(Json::String(out_path)) => dir.join(out_path),
+                Some(_) => return Err(bad_field("out_path")),
+                None => {
+                    return Err(ManifestError::MissingField {
+                        job,
+                        field: "out_path",
+                    })
+                }
+            };
+
+            Ok(Job {
+                strategy,
+                palette_size,
+                tile_size,
+                out_path,
+            })
+        })
+        .collect()
+}
+
+/// The reasons why [`read_manifest_file`] can fail.
+#[derive(Debug)]
+pub enum ManifestError {
+    /// The file could not be read.
+    Io(std::io::Error),
+    /// The file is not valid JSON; the error was detected on that (1-based) line.
+    Syntax(usize),
+    /// The manifest isn't an array of jobs.
+    NotAList,
+    /// The job of that (0-based) index lacks a required field.
+    MissingField { job: usize, field: &'static str },
+    /// The field of the job of that (0-based) index has the wrong type (e.g. a number instead of a string).
+    BadField { job: usize, field: &'static str },
+    /// The job of that (0-based) index names a strategy that doesn't exist.
+    UnknownStrategy { job: usize, err: UnknownStrategy },
+}
+
+impl Display for ManifestError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Io(err) => write!(f, "{err}"),
+            Self::Syntax(line_no) => write!(f, "Invalid JSON on line {line_no}"),
+            Self::NotAList => write!(f, "The manifest must be an array of jobs"),
+            Self::MissingField { job, field } => write!(f, "Job #{job} lacks a `{field}` field"),
+            Self::BadField { job, field } => {
+                write!(f, "Field `{field}` of job #{job} has an unexpected type")
+            }
+            Self::UnknownStrategy { job, err } => write!(f, "Job #{job}: {err}"),
+        }
+    }
+}
+
+impl std::error::Error for ManifestError {}
@@ -0,0 +1,641 @@
+/*
+    no_packsaran — Generator of images to defeat
+    Copyright (C) 2024  Eldred “ISSOtm” Habert <agpl@eldred.fr>
+
+    This program is free software: you can redistribute it and/or modify
+    it under the terms of the GNU Affero General Public License as published by
+    the Free Software Foundation, either version 3 of the License, or
+    (at your option) any later version.
+
+    This program is distributed in the hope that it will be useful,
+    but WITHOUT ANY WARRANTY; without even the implied warranty of
+    MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+    GNU Affero General Public License for more details.
+
+    You should have received a copy of the GNU Affero General Public License
+    along with this program.  If not, see <https://www.gnu.org/licenses/>.
+*/
+
+//! Reference implementations of the greedy packing algorithms that the strategies target,
+//! so that we can check that the generated images actually defeat them.
+
+use std::{cmp::Reverse, collections::VecDeque, fmt::Display};
+
+use crate::{ProtoPalette, Strategy};
+
+/// Packs the proto-palettes using the greedy algorithm targeted by `strategy`, returning the palettes built.
+///
+/// Each palette may hold up to `palette_size` colours.
+/// Proto-palettes are processed in order, as this is what the strategies exploit.
+/// (Except for [`Strategy::BestFitDecreasing`], which first (stably) sorts them by decreasing size;
+/// [`Strategy::Superfamiconv`], which also drops the ones that are subsets of others before doing so;
+/// and [`Strategy::Dsatur`], [`Strategy::OverloadAndRemove`], [`Strategy::MergeSmallest`], and [`Strategy::ClusterThenFit`],
+/// which pick their own order.)
+///
+/// [`Strategy::Combined`] doesn't target any algorithm of its own, so this panics if given it;
+/// the strategies that it combines should be checked individually instead.
+pub fn pack_greedy(
+    strategy: Strategy,
+    tiles: &[ProtoPalette],
+    palette_size: usize,
+) -> Vec<Vec<u8>> {
+    assert_ne!(
+        strategy,
+        Strategy::Combined,
+        "The combined strategy has no packing algorithm of its own!"
+    );
+    if strategy == Strategy::Dsatur {
+        return pack_dsatur(tiles, palette_size);
+    }
+    if strategy == Strategy::OverloadAndRemove {
+        return pack_overload_and_remove(tiles, palette_size);
+    }
+    if strategy == Strategy::MergeSmallest {
+        return pack_merge_smallest(tiles, palette_size);
+    }
+    if strategy == Strategy::ClusterThenFit {
+        return pack_cluster_then_fit(tiles, palette_size);
+    }
+
+    let mut tiles: Vec<_> = tiles.iter().collect();
+    if strategy == Strategy::Superfamiconv {
+        // Only the first of identical proto-palettes is kept, so that they don't rule each other out.
+        let all = tiles.clone();
+        tiles = all
+            .iter()
+            .enumerate()
+            .filter(|&(i, tile)| {
+                !all.iter().enumerate().any(|(j, other)| {
+                    j != i
+                        && tile.colors().iter().all(|&color| other.contains(color))
+                        && (tile.len() < other.len() || j < i)
+                })
+            })
+            .map(|(_, tile)| *tile)
+            .collect();
+    }
+    if matches!(
+        strategy,
+        Strategy::BestFitDecreasing | Strategy::Superfamiconv
+    ) {
+        tiles.sort_by_key(|tile| Reverse(tile.len()));
+    }
+    if strategy == Strategy::FfdIndexTiebreak {
+        // Tiles of the same size go in order of their lowest colour, which is thus deterministic even with an unstable sort.
+        tiles.sort_by_key(|tile| (Reverse(tile.len()), tile.colors().iter().min().copied()));
+    }
+
+    let mut palettes: Vec<Vec<u8>> = vec![];
+    for tile in tiles {
+        let nb_palettes = palettes.len();
+        let candidates = palettes.iter().enumerate().filter_map(|(i, palette)| {
+            let nb_new = nb_new_colors(palette, tile);
+            (palette.len() + nb_new <= palette_size).then_some((i, palette.len(), nb_new))
+        });
+        let chosen = match strategy {
+            // "Any fit" is a family of algorithms; first fit is its most common member.
+            Strategy::AnyFit
+            | Strategy::FirstFit
+            | Strategy::Superfamiconv
+            | Strategy::Obj
+            | Strategy::FfdIndexTiebreak => candidates.map(|(i, ..)| i).next(),
+            Strategy::Dsatur
+            | Strategy::OverloadAndRemove
+            | Strategy::MergeSmallest
+            | Strategy::ClusterThenFit
+            | Strategy::Combined => unreachable!(),
+            // Pick the palette that the tile shares the most colours with, i.e. that gains the fewest.
+            // (`min_by_key` keeps the first of equal candidates.)
+            Strategy::BestFusion => candidates
+                .min_by_key(|&(_, _, nb_new)| nb_new)
+                .map(|(i, ..)| i),
+            // Pick the palette that will have the most room left afterwards.
+            Strategy::WorstFit => candidates
+                .min_by_key(|&(_, len, nb_new)| len + nb_new)
+                .map(|(i, ..)| i),
+            // Only the last palette opened is ever considered.
+            Strategy::NextFit => candidates.map(|(i, ..)| i).find(|&i| i + 1 == nb_palettes),
+            // Pick the palette that will have the least room left afterwards.
+            Strategy::BestFitDecreasing => candidates
+                .min_by_key(|&(_, len, nb_new)| Reverse(len + nb_new))
+                .map(|(i, ..)| i),
+        };
+
+        match chosen {
+            Some(i) => add_colors(&mut palettes[i], tile),
+            None => palettes.push(tile.colors().to_vec()),
+        }
+    }
+    palettes
+}
+
+/// Packs the tiles, each given as the colours it uses, using first-fit: the reference opponent of [`crate::defeat_any_fit`].
+///
+/// Each tile goes in the first palette whose union with it still fits in `palette_size` colours, or else in a new palette.
+/// This is [`pack_greedy`] with [`Strategy::AnyFit`], for callers that don't deal in [`ProtoPalette`]s.
+///
+/// ```
+/// use no_packsaran::pack_any_fit;
+///
+/// let tiles = [vec![0, 1], vec![2, 3], vec![0, 2]];
+/// assert_eq!(pack_any_fit(&tiles, 4), [vec![0, 1, 2, 3]]);
+/// assert_eq!(pack_any_fit(&tiles, 2), [vec![0, 1], vec![2, 3], vec![0, 2]]);
+/// ```
+pub fn pack_any_fit(tiles: &[Vec<u8>], palette_size: usize) -> Vec<Vec<u8>> {
+    let tiles: Vec<ProtoPalette> = tiles.iter().cloned().map(ProtoPalette::new).collect();
+    pack_greedy(Strategy::AnyFit, &tiles, palette_size)
+}
+
+/// Packs the tiles, each given as the colours it uses, using best fusion: the reference opponent of [`crate::defeat_best_fusion`].
+///
+/// The tiles are taken in order, and each joins the palette that it shares the most colours with
+/// (i.e. that gains the fewest new colours), among those that still fit in `palette_size` colours with it;
+/// the first such palette wins ties, and a tile that fits in none opens a new palette.
+/// (Tiles never leave their palette, and palettes never get merged together, unlike with [`Strategy::MergeSmallest`]'s packer.)
+/// This is [`pack_greedy`] with [`Strategy::BestFusion`], for callers that don't deal in [`ProtoPalette`]s.
+///
+/// ```
+/// use no_packsaran::{pack_any_fit, pack_best_fusion};
+///
+/// // The last tile would fit in the first palette, but the second one already has its colour.
+/// let tiles = [vec![0, 1, 2], vec![3, 4, 5], vec![3]];
+/// assert_eq!(pack_best_fusion(&tiles, 4), [vec![0, 1, 2], vec![3, 4, 5]]);
+/// assert_eq!(pack_any_fit(&tiles, 4), [vec![0, 1, 2, 3], vec![3, 4, 5]]);
+/// ```
+pub fn pack_best_fusion(tiles: &[Vec<u8>], palette_size: usize) -> Vec<Vec<u8>> {
+    let tiles: Vec<ProtoPalette> = tiles.iter().cloned().map(ProtoPalette::new).collect();
+    pack_greedy(Strategy::BestFusion, &tiles, palette_size)
+}
+
+/// Packs the proto-palettes using first-fit over palettes of the given `sizes`, which [`crate::defeat_mixed`] targets.
+///
+/// Proto-palettes are processed in order, each going in the first palette that has room for it.
+/// The returned palettes start with one per size, in order (possibly empty);
+/// any proto-palette that fits in none of them gets an extra palette as large as the largest size, which the following ones may share.
+pub fn pack_mixed(tiles: &[ProtoPalette], sizes: &[usize]) -> Vec<Vec<u8>> {
+    let extra_size = sizes.iter().copied().max().unwrap_or(0);
+    let mut palettes: Vec<Vec<u8>> = vec![vec![]; sizes.len()];
+    for tile in tiles {
+        let chosen = palettes.iter().enumerate().position(|(i, palette)| {
+            let size = sizes.get(i).copied().unwrap_or(extra_size);
+            palette.len() + nb_new_colors(palette, tile) <= size
+        });
+        match chosen {
+            Some(i) => add_colors(&mut palettes[i], tile),
+            None => palettes.push(tile.colors().to_vec()),
+        }
+    }
+    palettes
+}
+
+/// The most distinct proto-palettes that [`pack_optimal`] accepts, as it takes exponential time.
+pub const MAX_EXACT_PROTO_PALETTES: usize = 24;
+
+/// Packs the proto-palettes into as few palettes of `palette_size` colours as possible, by brute force.
+///
+/// This tries to fit all proto-palettes into 1 palette, then 2, etc., backtracking through every assignment each time.
+/// That takes exponential time, so this refuses to deal with more than [`MAX_EXACT_PROTO_PALETTES`] distinct proto-palettes
+/// (identical ones always share a palette, so they only count once).
+/// Like the greedy packers, any proto-palette that's larger than `palette_size` gets a palette to itself.
+pub fn pack_optimal(
+    tiles: &[ProtoPalette],
+    palette_size: usize,
+) -> Result<Vec<Vec<u8>>, TooManyProtoPalettes> {
+    let mut sets: Vec<ColorSet> = tiles.iter().map(ColorSet::of).collect();
+    sets.sort_unstable();
+    sets.dedup();
+    if sets.len() > MAX_EXACT_PROTO_PALETTES {
+        return Err(TooManyProtoPalettes(sets.len()));
+    }
+    let (mut oversized, mut sets): (Vec<_>, Vec<_>) =
+        sets.into_iter().partition(|set| set.len() > palette_size);
+    // Placing the largest ones first makes dead ends apparent sooner.
+    sets.sort_by_key(|set| Reverse(set.len()));
+    // The colours used from each proto-palette onwards, which will need to find room somewhere.
+    let mut remaining = vec![ColorSet::default(); sets.len() + 1];
+    for i in (0..sets.len()).rev() {
+        remaining[i] = remaining[i + 1].union(sets[i]);
+    }
+
+    let mut palettes = vec![];
+    // Every colour needs a slot in at least one palette, so fewer palettes can't possibly be enough.
+    let lower_bound = remaining[0].len().div_ceil(palette_size.max(1));
+    for max in lower_bound..=sets.len() {
+        let search = Search {
+            sets: &sets,
+            remaining: &remaining,
+            palette_size,
+            max,
+        };
+        if search.place(0, &mut palettes) {
+            break;
+        }
+    }
+    palettes.append(&mut oversized);
+    Ok(palettes.iter().map(ColorSet::colors).collect())
+}
+
+/// Finds the fewest palettes of `palette_size` colours that can display every tile, each given as the colours it uses.
+///
+/// This is the reference "correct" packer, which the greedy ones can be checked against, e.g. after reading tiles back from an image.
+/// It returns `None` if there is no answer to give: either some tile has more than `palette_size` colours,
+/// or there are more than [`MAX_EXACT_PROTO_PALETTES`] distinct tiles (see [`pack_optimal`], which this uses).
+///
+/// The search is exponential in the number of distinct tiles, so even below that limit, it may take a while
+/// (up to a few seconds) if the palettes can be filled up in many almost-correct ways.
+///
+/// ```
+/// use no_packsaran::minimal_palettes;
+///
+/// let tiles = [vec![0, 1], vec![2, 3], vec![1, 2]];
+/// assert_eq!(minimal_palettes(&tiles, 4).map(|palettes| palettes.len()), Some(1));
+/// assert_eq!(minimal_palettes(&tiles, 3).map(|palettes| palettes.len()), Some(2));
+/// assert_eq!(minimal_palettes(&tiles, 1), None);
+/// ```
+pub fn minimal_palettes(tiles: &[Vec<u8>], palette_size: usize) -> Option<Vec<Vec<u8>>> {
+    let tiles: Vec<ProtoPalette> = tiles.iter().cloned().map(ProtoPalette::new).collect();
+    if tiles.iter().any(|tile| tile.len() > palette_size) {
+        return None;
+    }
+    pack_optimal(&tiles, palette_size).ok()
+}
+
+/// The fixed parameters of [`pack_optimal`]'s backtracking, for palettes of `palette_size` colours, up to `max` of them.
+struct Search<'a> {
+    sets: &'a [ColorSet],
+    remaining: &'a [ColorSet],
+    palette_size: usize,
+    max: usize,
+}
+
+impl Search<'_> {
+    /// Tries to add the proto-palettes from `next` onwards to `palettes`.
+    ///
+    /// On success, `palettes` holds the result; otherwise, it's left as it was.
+    fn place(&self, next: usize, palettes: &mut Vec<ColorSet>) -> bool {
+        let Some(&set) = self.sets.get(next) else {
+            return true;
+        };
+        // Give up early if the colours that aren't in any palette yet can't all fit in the room left.
+        // (A palette's room is only counted if one of the remaining proto-palettes still fits in it.)
+        let placed = palettes
+            .iter()
+            .fold(ColorSet::default(), |acc, &palette| acc.union(palette));
+        let nb_needed = self.remaining[next].union(placed).len() - placed.len();
+        let nb_free = palettes
+            .iter()
+            .filter(|palette| {
+                self.sets[next..]
+                    .iter()
+                    .any(|&set| palette.union(set).len() <= self.palette_size)
+            })
+            .map(|palette| self.palette_size - palette.len())
+            .sum::<usize>()
+            + (self.max - palettes.len()) * self.palette_size;
+        if nb_needed > nb_free {
+            return false;
+        }
+
+        for p in 0..palettes.len() {
+            let union = palettes[p].union(set);
+            // Identical palettes are interchangeable, so only the first of them is worth trying.
+            if union.len() > self.palette_size || palettes[..p].contains(&palettes[p]) {
+                continue;
+            }
+            let old = std::mem::replace(&mut palettes[p], union);
+            if self.place(next + 1, palettes) {
+                return true;
+            }
+            palettes[p] = old;
+        }
+        if palettes.len() < self.max {
+            palettes.push(set);
+            if self.place(next + 1, palettes) {
+                return true;
+            }
+            palettes.pop();
+        }
+        false
+    }
+}
+
+/// Returned by [`pack_optimal`] when given more than [`MAX_EXACT_PROTO_PALETTES`] distinct proto-palettes (and how many).
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct TooManyProtoPalettes(pub usize);
+
+impl Display for TooManyProtoPalettes {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "Brute-forcing {} distinct proto-palettes would take too long (at most {MAX_EXACT_PROTO_PALETTES} are supported)",
+            self.0
+        )
+    }
+}
+
+impl std::error::Error for TooManyProtoPalettes {}
+
+/// A set of colour indices, as a bitset, so that unions are cheap enough to brute-force with.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Default)]
+struct ColorSet([u128; 2]);
+
+impl ColorSet {
+    fn of(tile: &ProtoPalette) -> Self {
+        let mut bits = [0; 2];
+        for &color in tile.colors() {
+            bits[usize::from(color >> 7)] |= 1 << (color & 127);
+        }
+        Self(bits)
+    }
+
+    fn len(&self) -> usize {
+        self.0.iter().map(|bits| bits.count_ones() as usize).sum()
+    }
+
+    fn union(self, other: Self) -> Self {
+        Self([self.0[0] | other.0[0], self.0[1] | other.0[1]])
+    }
+
+    fn colors(&self) -> Vec<u8> {
+        (0..=u8::MAX)
+            .filter(|&color| self.0[usize::from(color >> 7)] & 1 << (color & 127) != 0)
+            .collect()
+    }
+}
+
+/// DSATUR treats packing as colouring a graph whose vertices are the tiles,
+/// two tiles being adjacent if they can't share a palette (i.e. they have too many colours together).
+///
+/// 1. Pick the tile adjacent to the most distinct palettes (its "saturation"),
+///    breaking ties by how many not-yet-packed tiles it's adjacent to, then by order.
+/// 2. Put it in the first palette that it fits in, or a new one.
+/// 3. Repeat until all tiles are packed.
+fn pack_dsatur(tiles: &[ProtoPalette], palette_size: usize) -> Vec<Vec<u8>> {
+    let adjacent: Vec<Vec<usize>> = tiles
+        .iter()
+        .map(|tile| {
+            (0..tiles.len())
+                .filter(|&j| tiles[j].len() + nb_new_colors(tiles[j].colors(), tile) > palette_size)
+                .collect()
+        })
+        .collect();
+
+    let mut assignment = vec![None; tiles.len()];
+    let mut palettes: Vec<Vec<u8>> = vec![];
+    for _ in 0..tiles.len() {
+        let (next, ..) = (0..tiles.len())
+            .filter(|&i| assignment[i].is_none())
+            .map(|i| {
+                let mut neighbour_palettes: Vec<usize> =
+                    adjacent[i].iter().filter_map(|&j| assignment[j]).collect();
+                neighbour_palettes.sort_unstable();
+                neighbour_palettes.dedup();
+                let degree = adjacent[i]
+                    .iter()
+                    .filter(|&&j| assignment[j].is_none())
+                    .count();
+                (i, neighbour_palettes.len(), degree)
+            })
+            .max_by_key(|&(i, saturation, degree)| (saturation, degree, Reverse(i)))
+            .expect("There is at least one tile left to pack");
+
+        let tile = &tiles[next];
+        let chosen = palettes
+            .iter()
+            .position(|palette| palette.len() + nb_new_colors(palette, tile) <= palette_size);
+        assignment[next] = Some(match chosen {
+            Some(i) => {
+                add_colors(&mut palettes[i], tile);
+                i
+            }
+            None => {
+                palettes.push(tile.colors().to_vec());
+                palettes.len() - 1
+            }
+        });
+    }
+    palettes
+}
+
+/// RGBGFX's "overload-and-remove", whose phases are described in [`crate::defeat_overload_and_remove`].
+///
+/// (The real thing also decants groups of proto-palettes that share colours together; this only moves them one at a time.)
+fn pack_overload_and_remove(tiles: &[ProtoPalette], palette_size: usize) -> Vec<Vec<u8>> {
+    let mut queue: VecDeque<usize> = (0..tiles.len()).collect();
+    queue
+        .make_contiguous()
+        .sort_by_key(|&i| Reverse(tiles[i].len()));
+    // Which palettes each proto-palette has been kicked out of.
+    let mut banned = vec![vec![]; tiles.len()];
+
+    let mut palettes: Vec<Vec<usize>> = vec![];
+    while let Some(i) = queue.pop_front() {
+        // `min_by` keeps the first of equal candidates.
+        let chosen = (0..palettes.len())
+            .filter(|p| !banned[i].contains(p))
+            .map(|p| (p, rel_size(tiles, &palettes[p], i)))
+            .min_by(|(_, a), (_, b)| a.total_cmp(b));
+        let Some((p, _)) = chosen else {
+            palettes.push(vec![i]);
+            continue;
+        };
+
+        let palette = &mut palettes[p];
+        palette.push(i);
+        while union(tiles, palette.iter().copied()).len() > palette_size {
+            let efficiencies: Vec<f64> = palette
+                .iter()
+                .map(|&j| tiles[j].len() as f64 / rel_size(tiles, palette, j))
+                .collect();
+            let (worst, &min) = efficiencies
+                .iter()
+                .enumerate()
+                .min_by(|(_, a), (_, b)| a.total_cmp(b))
+                .expect("The palette is overloaded, so it can't be empty");
+            if efficiencies.iter().all(|&efficiency| efficiency == min) {
+                break; // Nothing stands out, so give up on this palette.
+            }
+            let j = palette.remove(worst);
+            banned[j].push(p);
+            queue.push_back(j);
+        }
+    }
+
+    let fits = |palette: &[usize], extra: &[usize]| {
+        union(tiles, palette.iter().chain(extra).copied()).len() <= palette_size
+    };
+    let mut leftovers = vec![];
+    for palette in &mut palettes {
+        if !fits(palette, &[]) {
+            leftovers.append(palette);
+        }
+    }
+    palettes.retain(|palette| !palette.is_empty());
+    for j in leftovers {
+        match palettes.iter().position(|palette| fits(palette, &[j])) {
+            Some(p) => palettes[p].push(j),
+            None => palettes.push(vec![j]),
+        }
+    }
+
+    // Decant until nothing moves anymore.
+    let mut changed = true;
+    while changed {
+        changed = false;
+        for to in 0..palettes.len() {
+            for from in to + 1..palettes.len() {
+                if !palettes[from].is_empty() && fits(&palettes[to], &palettes[from]) {
+                    let moved = std::mem::take(&mut palettes[from]);
+                    palettes[to].extend(moved);
+                    changed = true;
+                }
+                let mut k = 0;
+                while k < palettes[from].len() {
+                    if fits(&palettes[to], &palettes[from][k..=k]) {
+                        let moved = palettes[from].remove(k);
+                        palettes[to].push(moved);
+                        changed = true;
+                    } else {
+                        k += 1;
+                    }
+                }
+            }
+        }
+        palettes.retain(|palette| !palette.is_empty());
+    }
+
+    palettes
+        .iter()
+        .map(|palette| union(tiles, palette.iter().copied()))
+        .collect()
+}
+
+/// Agglomerative clustering, as described in [`crate::defeat_merge_smallest`]:
+/// starting from one palette per distinct proto-palette, merge the pair with the smallest union until none fit together.
+fn pack_merge_smallest(tiles: &[ProtoPalette], palette_size: usize) -> Vec<Vec<u8>> {
+    let mut palettes: Vec<Vec<u8>> = vec![];
+    for tile in tiles {
+        if !palettes.iter().any(|palette| {
+            palette.len() == tile.len() && tile.colors().iter().all(|color| palette.contains(color))
+        }) {
+            palettes.push(tile.colors().to_vec());
+        }
+    }
+
+    loop {
+        // `min_by_key` keeps the first of equal candidates.
+        let chosen = (0..palettes.len())
+            .flat_map(|i| (i + 1..palettes.len()).map(move |j| (i, j)))
+            .map(|(i, j)| {
+                let nb_new = palettes[j]
+                    .iter()
+                    .filter(|color| !palettes[i].contains(color))
+                    .count();
+                (i, j, palettes[i].len() + nb_new)
+            })
+            .filter(|&(.., len)| len <= palette_size)
+            .min_by_key(|&(.., len)| len);
+        let Some((i, j, _)) = chosen else {
+            return palettes;
+        };
+        let merged = palettes.remove(j);
+        for color in merged {
+            if !palettes[i].contains(&color) {
+                palettes[i].push(color);
+            }
+        }
+    }
+}
+
+/// The two-phase packer described in [`crate::defeat_cluster_then_fit`]: cluster the proto-palettes that share the most colours,
+/// then pack the clusters using first-fit-decreasing.
+fn pack_cluster_then_fit(tiles: &[ProtoPalette], palette_size: usize) -> Vec<Vec<u8>> {
+    let mut clusters: Vec<Vec<u8>> = vec![];
+    for tile in tiles {
+        if !clusters.iter().any(|cluster| {
+            cluster.len() == tile.len() && tile.colors().iter().all(|color| cluster.contains(color))
+        }) {
+            clusters.push(tile.colors().to_vec());
+        }
+    }
+
+    loop {
+        // `max_by_key` keeps the *last* of equal candidates, hence the `Reverse`.
+        let chosen = (0..clusters.len())
+            .flat_map(|i| (i + 1..clusters.len()).map(move |j| (i, j)))
+            .map(|(i, j)| {
+                let nb_shared = clusters[j]
+                    .iter()
+                    .filter(|color| clusters[i].contains(color))
+                    .count();
+                (i, j, nb_shared)
+            })
+            .filter(|&(i, j, nb_shared)| {
+                nb_shared != 0 && clusters[i].len() + clusters[j].len() - nb_shared <= palette_size
+            })
+            .max_by_key(|&(i, j, nb_shared)| (nb_shared, Reverse((i, j))));
+        let Some((i, j, _)) = chosen else {
+            break;
+        };
+        let merged = clusters.remove(j);
+        for color in merged {
+            if !clusters[i].contains(&color) {
+                clusters[i].push(color);
+            }
+        }
+    }
+
+    clusters.sort_by_key(|cluster| Reverse(cluster.len()));
+    let mut palettes: Vec<Vec<u8>> = vec![];
+    for cluster in clusters {
+        let cluster = ProtoPalette::new(cluster);
+        match palettes
+            .iter()
+            .position(|palette| palette.len() + nb_new_colors(palette, &cluster) <= palette_size)
+        {
+            Some(i) => add_colors(&mut palettes[i], &cluster),
+            None => palettes.push(cluster.colors().to_vec()),
+        }
+    }
+    palettes
+}
+
+/// How much of a palette's room the tile would take up, counting colours shared with `n` of its proto-palettes as `1 / (n + 1)`.
+fn rel_size(tiles: &[ProtoPalette], members: &[usize], tile: usize) -> f64 {
+    tiles[tile]
+        .colors()
+        .iter()
+        .map(|&color| {
+            let nb_sharing = members
+                .iter()
+                .filter(|&&member| tiles[member].contains(color))
+                .count();
+            1.0 / (nb_sharing + 1) as f64
+        })
+        .sum()
+}
+
+/// The colours used by the given proto-palettes together.
+fn union(tiles: &[ProtoPalette], members: impl IntoIterator<Item = usize>) -> Vec<u8> {
+    let mut palette = vec![];
+    for member in members {
+        add_colors(&mut palette, &tiles[member]);
+    }
+    palette
+}
+
+/// How many of the tile's colours the palette doesn't have yet.
+fn nb_new_colors(palette: &[u8], tile: &ProtoPalette) -> usize {
+    tile.colors()
+        .iter()
+        .filter(|color| !palette.contains(color))
+        .count()
+}
+
+fn add_colors(palette: &mut Vec<u8>, tile: &ProtoPalette) {
+    for &color in tile.colors() {
+        if !palette.contains(&color) {
+            palette.push(color);
+        }
+    }
+}
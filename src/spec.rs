@@ -0,0 +1,396 @@
+/*
+    no_packsaran — Generator of images to defeat
+    Copyright (C) 2024  Eldred “ISSOtm” Habert <agpl@eldred.fr>
+
+    This program is free software: you can redistribute it and/or modify
+    it under the terms of the GNU Affero General Public License as published by
+    the Free Software Foundation, either version 3 of the License, or
+    (at your option) any later version.
+
+    This program is distributed in the hope that it will be useful,
+    but WITHOUT ANY WARRANTY; without even the implied warranty of
+    MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+    GNU Affero General Public License for more details.
+
+    You should have received a copy of the GNU Affero General Public License
+    along with this program.  If not, see <https://www.gnu.org/licenses/>.
+*/
+
+//! Machine-readable descriptions of attacks ("specs"), as JSON.
+//!
+//! The format is simple enough that it's written by hand, which spares the crate from depending on `serde`.
+
+use std::{fmt::Display, path::Path};
+
+use crate::{Attack, ProtoPalette, Strategy};
+
+/// Everything that describes a generated image, save for its colours and pixel layout.
+#[derive(Debug, Clone, Copy)]
+pub struct Spec<'a> {
+    /// The strategy that generated the tiles, if they weren't provided by the user instead.
+    pub strategy: Option<Strategy>,
+    /// How many colours each palette can hold.
+    pub palette_size: usize,
+    pub tile_width: usize,
+    pub tile_height: usize,
+    pub attack: &'a Attack,
+    /// The name of each colour, by index, if it has one (see [`crate::read_named_palette_file`]).
+    /// This may be shorter than the attack's colours, e.g. empty if they have no names.
+    pub color_names: &'a [Option<String>],
+}
+
+/// Writes the spec to `path`, as a JSON object (see [`spec_json`]).
+pub fn write_spec_json(spec: &Spec, path: &Path) -> std::io::Result<()> {
+    std::fs::write(path, spec_json(spec))
+}
+
+/// Formats the spec as a JSON object.
+///
+/// It contains the strategy's name (or `null`), the `palette_size`, the `tile_size` (as `[width, height]`),
+/// `nb_colors`, the proto-palette of every `tiles`, the `optimal_palettes`,
+/// and the `color_names` (an object mapping the indices of the colours that have a name to it).
+pub fn spec_json(spec: &Spec) -> String {
+    let mut json = String::from("{\n");
+    match spec.strategy {
+        Some(strategy) => json.push_str(&format!("\t\"strategy\": \"{}\",\n", strategy.name())),
+        None => json.push_str("\t\"strategy\": null,\n"),
+    }
+    json.push_str(&format!("\t\"palette_size\": {},\n", spec.palette_size));
+    json.push_str(&format!(
+        "\t\"tile_size\": [{}, {}],\n",
+        spec.tile_width, spec.tile_height
+    ));
+    json.push_str(&format!("\t\"nb_colors\": {},\n", spec.attack.nb_colors));
+    json.push_str("\t\"tiles\": ");
+    push_index_lists(
+        &mut json,
+        spec.attack.tiles.iter().map(|tile| tile.colors()),
+    );
+    json.push_str(",\n\t\"optimal_palettes\": ");
+    push_index_lists(
+        &mut json,
+        spec.attack.optimal_palettes.iter().map(Vec::as_slice),
+    );
+    json.push_str(",\n\t\"color_names\": {");
+    let names = spec
+        .color_names
+        .iter()
+        .enumerate()
+        .filter_map(|(i, name)| Some((i, name.as_deref()?)));
+    for (n, (i, name)) in names.enumerate() {
+        if n != 0 {
+            json.push(',');
+        }
+        json.push_str(&format!("\"{i}\":"));
+        push_string(&mut json, name);
+    }
+    json.push_str("}\n}\n");
+    json
+}
+
+/// Appends a JSON array of arrays of colour indices to `json`.
+pub(crate) fn push_index_lists<'a>(json: &mut String, lists: impl IntoIterator<Item = &'a [u8]>) {
+    json.push('[');
+    for (i, list) in lists.into_iter().enumerate() {
+        if i != 0 {
+            json.push(',');
+        }
+        json.push('[');
+        for (j, color) in list.iter().enumerate() {
+            if j != 0 {
+                json.push(',');
+            }
+            json.push_str(&color.to_string());
+        }
+        json.push(']');
+    }
+    json.push(']');
+}
+
+/// Appends `string` to `json` as a JSON string, escaping it as necessary.
+fn push_string(json: &mut String, string: &str) {
+    json.push('"');
+    for c in string.chars() {
+        match c {
+            '"' => json.push_str("\\\""),
+            '\\' => json.push_str("\\\\"),
+            '\n' => json.push_str("\\n"),
+            '\t' => json.push_str("\\t"),
+            '\r' => json.push_str("\\r"),
+            c if c.is_control() => json.push_str(&format!("\\u{:04x}", u32::from(c))),
+            c => json.push(c),
+        }
+    }
+    json.push('"');
+}
+
+/// Reads the tiles to generate from a JSON spec, of the form `{ "nb_colors": N, "tiles": [[0, 1], [2, 3]] }`.
+///
+/// This allows rendering hand-crafted proto-palettes instead of a strategy's.
+/// If the spec also has `optimal_palettes` (like the ones written by [`write_spec_json`]), they are used too;
+/// otherwise, the returned attack has none. Any other fields are ignored.
+pub fn read_spec_file(path: &Path) -> Result<Attack, SpecFileError> {
+    let contents = std::fs::read_to_string(path).map_err(SpecFileError::Io)?;
+    let spec = Parser::parse(&contents)?;
+    let Json::Object(fields) = spec else {
+        return Err(SpecFileError::BadField("(root)"));
+    };
+    let field = |name| fields.iter().find(|(key, _)| key == name).map(|(_, v)| v);
+
+    let nb_colors = field("nb_colors").ok_or(SpecFileError::MissingField("nb_colors"))?;
+    let nb_colors = nb_colors
+        .as_integer()
+        .ok_or(SpecFileError::BadField("nb_colors"))?;
+    let nb_colors = u8::try_from(nb_colors).map_err(|_| SpecFileError::TooManyColors(nb_colors))?;
+
+    let tiles = field("tiles").ok_or(SpecFileError::MissingField("tiles"))?;
+    let tiles = index_lists(tiles, nb_colors, "tiles")?;
+    // A tile must have at least one colour to draw its pixels with.
+    if let Some(index) = tiles.iter().position(Vec::is_empty) {
+        return Err(SpecFileError::EmptyTile(index));
+    }
+    let tiles = tiles.into_iter().map(ProtoPalette::new).collect();
+    let optimal_palettes = match field("optimal_palettes") {
+        Some(palettes) => index_lists(palettes, nb_colors, "optimal_palettes")?,
+        None => vec![],
+    };
+
+    Ok(Attack {
+        tiles,
+        nb_colors,
+        optimal_palettes,
+    })
+}
+
+/// Checks that `value` is an array of arrays of colour indices below `nb_colors`, and collects them.
+fn index_lists(
+    value: &Json,
+    nb_colors: u8,
+    field: &'static str,
+) -> Result<Vec<Vec<u8>>, SpecFileError> {
+    let Json::Array(lists) = value else {
+        return Err(SpecFileError::BadField(field));
+    };
+    lists
+        .iter()
+        .enumerate()
+        .map(|(i, list)| {
+            let Json::Array(colors) = list else {
+                return Err(SpecFileError::BadField(field));
+            };
+            colors
+                .iter()
+                .enumerate()
+                .map(|(position, color)| {
+                    let color = color.as_integer().ok_or(SpecFileError::BadField(field))?;
+                    u8::try_from(color)
+                        .ok()
+                        .filter(|&color| color < nb_colors)
+                        .ok_or(SpecFileError::ColorOutOfRange {
+                            field,
+                            index: i,
+                            position,
+                            color,
+                            nb_colors,
+                        })
+                })
+                .collect()
+        })
+        .collect()
+}
+
+/// The reasons why [`read_spec_file`] can fail.
+#[derive(Debug)]
+pub enum SpecFileError {
+    /// The file could not be read.
+    Io(std::io::Error),
+    /// The file is not valid JSON; the error was detected on that (1-based) line.
+    Syntax(usize),
+    /// The spec lacks a required field.
+    MissingField(&'static str),
+    /// The field has the wrong type (e.g. a string instead of an array).
+    BadField(&'static str),
+    /// Colour indices are stored as bytes, so there can't be that many colours.
+    TooManyColors(u64),
+    /// The entry of that (0-based) index in `field` uses a colour that's not below `nb_colors`,
+    /// at that (0-based) position within the entry.
+    ColorOutOfRange {
+        field: &'static str,
+        index: usize,
+        position: usize,
+        color: u64,
+        nb_colors: u8,
+    },
+    /// The tile of that (0-based) index has no colours.
+    EmptyTile(usize),
+}
+
+impl Display for SpecFileError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Io(err) => write!(f, "{err}"),
+            Self::Syntax(line_no) => write!(f, "Invalid JSON on line {line_no}"),
+            Self::MissingField(field) => write!(f, "Missing `{field}` field"),
+            Self::BadField(field) => write!(f, "Field `{field}` has an unexpected type"),
+            Self::TooManyColors(nb_colors) => {
+                write!(f, "At most 255 colours are supported, not {nb_colors}")
+            }
+            Self::ColorOutOfRange {
+                field,
+                index,
+                position,
+                color,
+                nb_colors,
+            } => write!(
+                f,
+                "Entry #{index} of `{field}` uses colour {color} at position {position}, but there are only {nb_colors} colours"
+            ),
+            Self::EmptyTile(index) => write!(f, "Tile #{index} has no colours"),
+        }
+    }
+}
+
+impl std::error::Error for SpecFileError {}
+
+/// Just enough of JSON to read specs (and manifests) with.
+#[derive(Debug)]
+pub(crate) enum Json {
+    /// `null` or a boolean; neither specs nor manifests use any of them, so their values aren't kept.
+    Other,
+    Number(f64),
+    String(String),
+    Array(Vec<Json>),
+    Object(Vec<(String, Json)>),
+}
+
+impl Json {
+    pub(crate) fn as_integer(&self) -> Option<u64> {
+        match *self {
+            Self::Number(number) if number >= 0.0 && number.fract() == 0.0 => Some(number as u64),
+            _ => None,
+        }
+    }
+}
+
+/// How deeply arrays and objects may be nested; specs and manifests need 3 levels at most (e.g. a spec's tiles),
+/// and anything much deeper would only recurse until the stack overflows.
+const MAX_DEPTH: usize = 8;
+
+/// A recursive descent JSON parser.
+pub(crate) struct Parser<'a> {
+    text: &'a str,
+    pos: usize,
+}
+
+impl<'a> Parser<'a> {
+    pub(crate) fn parse(text: &'a str) -> Result<Json, SpecFileError> {
+        let mut parser = Self { text, pos: 0 };
+        let value = parser.value(0);
+        parser.skip_whitespace();
+        match value {
+            Some(value) if parser.pos == text.len() => Ok(value),
+            _ => {
+                let line_no = text[..parser.pos].matches('\n').count() + 1;
+                Err(SpecFileError::Syntax(line_no))
+            }
+        }
+    }
+
+    fn rest(&self) -> &'a str {
+        &self.text[self.pos..]
+    }
+
+    fn skip_whitespace(&mut self) {
+        let rest = self.rest();
+        self.pos += rest.len() - rest.trim_start_matches([' ', '\t', '\n', '\r']).len();
+    }
+
+    /// Consumes `token` (after any whitespace), if it's next.
+    fn eat(&mut self, token: &str) -> bool {
+        self.skip_whitespace();
+        let found = self.rest().starts_with(token);
+        if found {
+            self.pos += token.len();
+        }
+        found
+    }
+
+    /// Parses a value nested inside `depth` arrays and objects.
+    fn value(&mut self, depth: usize) -> Option<Json> {
+        if self.eat("null") || self.eat("true") || self.eat("false") {
+            Some(Json::Other)
+        } else if self.rest().starts_with(['[', '{']) && depth == MAX_DEPTH {
+            None
+        } else if self.eat("[") {
+            self.list("]", |parser| parser.value(depth + 1))
+                .map(Json::Array)
+        } else if self.eat("{") {
+            self.list("}", |parser| {
+                parser.skip_whitespace();
+                let key = parser.string()?;
+                parser.eat(":").then_some(())?;
+                Some((key, parser.value(depth + 1)?))
+            })
+            .map(Json::Object)
+        } else if self.rest().starts_with('"') {
+            self.string().map(Json::String)
+        } else {
+            let len = self
+                .rest()
+                .find(|c: char| !matches!(c, '0'..='9' | '-' | '+' | '.' | 'e' | 'E'))
+                .unwrap_or(self.rest().len());
+            let number = self.rest()[..len].parse().ok()?;
+            self.pos += len;
+            Some(Json::Number(number))
+        }
+    }
+
+    /// Parses comma-separated items, up to and including `end`; the opening bracket must have been consumed.
+    fn list<T>(
+        &mut self,
+        end: &str,
+        mut item: impl FnMut(&mut Self) -> Option<T>,
+    ) -> Option<Vec<T>> {
+        let mut items = vec![];
+        if self.eat(end) {
+            return Some(items);
+        }
+        loop {
+            items.push(item(self)?);
+            if self.eat(end) {
+                return Some(items);
+            }
+            self.eat(",").then_some(())?;
+        }
+    }
+
+    fn string(&mut self) -> Option<String> {
+        let mut chars = self.rest().strip_prefix('"')?.char_indices();
+        let mut string = String::new();
+        loop {
+            let (i, c) = chars.next()?;
+            match c {
+                '"' => {
+                    self.pos += 1 + i + 1; // Both quotes, and everything in-between.
+                    return Some(string);
+                }
+                '\\' => string.push(match chars.next()?.1 {
+                    'n' => '\n',
+                    't' => '\t',
+                    'r' => '\r',
+                    'b' => '\u{8}',
+                    'f' => '\u{c}',
+                    'u' => {
+                        let hex: String = chars.by_ref().take(4).map(|(_, c)| c).collect();
+                        let code = u32::from_str_radix(&hex, 16).ok()?;
+                        // Surrogate pairs aren't worth supporting, as strings are only names and paths, rarely outside of the BMP.
+                        char::from_u32(code).unwrap_or(char::REPLACEMENT_CHARACTER)
+                    }
+                    c @ ('"' | '\\' | '/') => c,
+                    _ => return None,
+                }),
+                c => string.push(c),
+            }
+        }
+    }
+}
@@ -0,0 +1,27 @@
+// Checks that first-fit, as the library implements it, is defeated by the any_fit strategy's tiles.
+
+use no_packsaran::{defeat_any_fit, pack_any_fit};
+
+#[test]
+fn any_fit_is_defeated() {
+    // Larger sizes have so many tiles that this would take a while.
+    for palette_size in [4, 6, 8] {
+        let attack = defeat_any_fit(palette_size).unwrap();
+        let tiles: Vec<Vec<u8>> = attack
+            .tiles
+            .iter()
+            .map(|tile| tile.colors().to_vec())
+            .collect();
+
+        let result = pack_any_fit(&tiles, palette_size);
+        assert_eq!(attack.nb_optimal_palettes(), 2);
+        assert!(
+            result.len() > 2,
+            "First-fit only needed {} palettes at size {palette_size}",
+            result.len()
+        );
+        for palette in &result {
+            assert!(palette.len() <= palette_size);
+        }
+    }
+}
@@ -0,0 +1,26 @@
+// Checks that best fusion, as the library implements it, needs more palettes than the best_fusion strategy's optimum.
+
+use no_packsaran::{defeat_best_fusion, pack_best_fusion};
+
+#[test]
+fn best_fusion_is_defeated() {
+    for palette_size in [4, 6, 8, 16] {
+        let attack = defeat_best_fusion(palette_size).unwrap();
+        let tiles: Vec<Vec<u8>> = attack
+            .tiles
+            .iter()
+            .map(|tile| tile.colors().to_vec())
+            .collect();
+
+        let result = pack_best_fusion(&tiles, palette_size);
+        assert!(
+            result.len() > attack.nb_optimal_palettes(),
+            "Best fusion only needed {} palettes at size {palette_size}, the optimum being {}",
+            result.len(),
+            attack.nb_optimal_palettes()
+        );
+        for palette in &result {
+            assert!(palette.len() <= palette_size);
+        }
+    }
+}
@@ -0,0 +1,41 @@
+// Checks that DSATUR, as the library implements it, is defeated by the dsatur strategy's tiles,
+// and that this comes from their conflicts rather than from their order.
+
+use no_packsaran::{defeat_dsatur, pack_greedy, worst_case_palettes, Strategy};
+
+#[test]
+fn dsatur_is_defeated() {
+    for palette_size in (4..=126).step_by(2) {
+        let attack = defeat_dsatur(palette_size).unwrap();
+
+        let result = pack_greedy(Strategy::Dsatur, &attack.tiles, palette_size);
+        assert_eq!(attack.nb_optimal_palettes(), 2);
+        assert_eq!(
+            result.len(),
+            worst_case_palettes(Strategy::Dsatur, palette_size),
+            "DSATUR needed {} palettes at size {palette_size}",
+            result.len()
+        );
+        for palette in &result {
+            assert!(palette.len() <= palette_size);
+        }
+    }
+}
+
+#[test]
+fn dsatur_tiles_conflict() {
+    let palette_size = 8;
+    let attack = defeat_dsatur(palette_size).unwrap();
+    let conflict = |a: usize, b: usize| {
+        let mut colors = [attack.tiles[a].colors(), attack.tiles[b].colors()].concat();
+        colors.sort_unstable();
+        colors.dedup();
+        colors.len() > palette_size
+    };
+    let degrees: Vec<usize> = (0..attack.tiles.len())
+        .map(|i| (0..attack.tiles.len()).filter(|&j| conflict(i, j)).count())
+        .collect();
+
+    // The most constrained tile is emitted last, so DSATUR's order can't be the emission order.
+    assert_eq!(degrees, [0, 0, 1, 1, 2]);
+}
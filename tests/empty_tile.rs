@@ -0,0 +1,19 @@
+// Checks that tiles without any colours are reported as an error, instead of dividing by zero when filling them.
+
+use std::io::ErrorKind;
+
+use no_packsaran::{build_palette, ImageBuilder, PaletteStyle, ProtoPalette};
+
+#[test]
+fn empty_tile_is_rejected() {
+    let palette = build_palette(2, PaletteStyle::default());
+    let tiles = [ProtoPalette::new(vec![0, 1]), ProtoPalette::new(vec![])];
+
+    let err = ImageBuilder::new()
+        .palette(&palette)
+        .tiles(&tiles)
+        .build_in_memory()
+        .unwrap_err();
+    assert_eq!(err.kind(), ErrorKind::InvalidInput);
+    assert_eq!(err.to_string(), "Tile #1 has no colours");
+}
@@ -0,0 +1,39 @@
+// Checks that the 2bpp tiles, attribute map, and palettes meant for the GBC display each tile with its own colours.
+
+use std::collections::BTreeSet;
+
+use no_packsaran::{
+    build_palette, defeat_any_fit, encode_gbc_2bpp, encode_gbc_attrmap, encode_gbc_palettes,
+    PaletteStyle, PixelOrder, TileFill,
+};
+
+#[test]
+fn tiles_show_their_colors() {
+    let attack = defeat_any_fit(4).unwrap();
+    let data = encode_gbc_2bpp(8, 8, &attack, TileFill::default(), PixelOrder::RowMajor).unwrap();
+    let attrmap = encode_gbc_attrmap(&attack).unwrap();
+    assert_eq!(data.len(), attack.tiles.len() * 16);
+    assert_eq!(attrmap.len(), attack.tiles.len());
+
+    for (i, (tile, tile_data)) in attack.tiles.iter().zip(data.chunks(16)).enumerate() {
+        let palette = &attack.optimal_palettes[usize::from(attrmap[i])];
+        // Each row is a byte of low bits, then a byte of high bits, leftmost pixel first.
+        let shown: BTreeSet<u8> = tile_data
+            .chunks(2)
+            .flat_map(|row| {
+                (0..8).map(move |x| (row[0] >> (7 - x) & 1) | (row[1] >> (7 - x) & 1) << 1)
+            })
+            .map(|index| palette[usize::from(index)])
+            .collect();
+        let expected: BTreeSet<u8> = tile.colors().iter().copied().collect();
+        assert_eq!(shown, expected, "Tile #{i} shows the wrong colours");
+    }
+}
+
+#[test]
+fn palettes_are_padded() {
+    let attack = defeat_any_fit(2).unwrap();
+    let palette = build_palette(attack.nb_colors, PaletteStyle::default());
+    // Two optimal palettes of 2 colours, each padded to 4 colours of 2 bytes.
+    assert_eq!(encode_gbc_palettes(&attack, &palette).unwrap().len(), 16);
+}
@@ -0,0 +1,62 @@
+// Checks that grouping each strategy's tiles by their optimal palette never overflows one,
+// and that tiles that fit in none of them get reported (and encoders do the same).
+
+use no_packsaran::{
+    encode_gbc_attrmap, optimal_assignment, proto_palettes, AssignmentError, Attack, EncodeError,
+    Strategy,
+};
+
+#[test]
+fn assignment_fits() {
+    for strategy in Strategy::ALL {
+        for palette_size in [4, 8, 16] {
+            if palette_size < strategy.min_palette_size() {
+                continue;
+            }
+            let assignment = match optimal_assignment(strategy, palette_size) {
+                Ok(assignment) => assignment,
+                // Not every strategy supports every size.
+                Err(AssignmentError::Defeat(_)) => continue,
+                Err(err) => panic!("{err}"),
+            };
+            let tiles: Vec<_> = proto_palettes(strategy, palette_size).unwrap().collect();
+            assert_eq!(assignment.len(), tiles.len(), "{}", strategy.name());
+
+            let nb_palettes = assignment.iter().max().map_or(0, |&max| max + 1);
+            let mut palettes = vec![vec![]; nb_palettes];
+            for (tile, &palette) in tiles.iter().zip(&assignment) {
+                palettes[palette].extend_from_slice(tile);
+            }
+            for (i, palette) in palettes.iter_mut().enumerate() {
+                palette.sort_unstable();
+                palette.dedup();
+                assert!(
+                    palette.len() <= palette_size,
+                    "{}'s palette #{i} has {} colours at size {palette_size}",
+                    strategy.name(),
+                    palette.len()
+                );
+            }
+        }
+    }
+}
+
+#[test]
+fn unassigned_tile_is_reported() {
+    // As a spec file could have it: the last tile mixes both palettes' colours.
+    let attack = Attack {
+        tiles: vec![vec![0, 1].into(), vec![2, 3].into(), vec![1, 2].into()],
+        nb_colors: 4,
+        optimal_palettes: vec![vec![0, 1], vec![2, 3]],
+    };
+    assert_eq!(
+        attack.optimal_assignment(),
+        Err(AssignmentError::Unassigned { tile: 2 })
+    );
+    assert!(matches!(
+        encode_gbc_attrmap(&attack),
+        Err(EncodeError::NoOptimalPalette(AssignmentError::Unassigned {
+            tile: 2
+        }))
+    ));
+}
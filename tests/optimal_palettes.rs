@@ -0,0 +1,17 @@
+// Checks the optimal palette counts that the strategies report against hand-computed ones.
+
+use no_packsaran::{defeat_any_fit, defeat_best_fusion};
+
+#[test]
+fn any_fit_needs_two_palettes() {
+    // The even colours, and the odd ones.
+    assert_eq!(defeat_any_fit(4).unwrap().nb_optimal_palettes(), 2);
+    assert_eq!(defeat_any_fit(6).unwrap().nb_optimal_palettes(), 2);
+}
+
+#[test]
+fn best_fusion_needs_one_more_than_half() {
+    // The A-palette, plus one per pair of (N-1)-combinations.
+    assert_eq!(defeat_best_fusion(4).unwrap().nb_optimal_palettes(), 3);
+    assert_eq!(defeat_best_fusion(6).unwrap().nb_optimal_palettes(), 4);
+}
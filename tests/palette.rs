@@ -0,0 +1,19 @@
+// Checks that the generated palettes never give two indices the same colour, which packers could then merge.
+
+use no_packsaran::{build_palette, PaletteStyle};
+
+#[test]
+fn colors_are_distinct() {
+    // Odd counts included, e.g. for the obj strategy's 2(N-1)+1 colours.
+    for nb_colors in 1..=255 {
+        let mut palette = build_palette(nb_colors, PaletteStyle::default());
+        assert_eq!(palette.len(), usize::from(nb_colors));
+        palette.sort_unstable_by_key(|color| color.0);
+        palette.dedup();
+        assert_eq!(
+            palette.len(),
+            usize::from(nb_colors),
+            "Some of the {nb_colors} colours are the same"
+        );
+    }
+}
@@ -0,0 +1,74 @@
+// Checks that palette sizes a strategy can't handle (too small or too large) are reported as errors, instead of panicking.
+
+use no_packsaran::{
+    defeat_any_fit, defeat_best_fit_decreasing, defeat_best_fusion, defeat_cluster_then_fit,
+    defeat_combined, defeat_dsatur, defeat_ffd_index_tiebreak, defeat_first_fit,
+    defeat_merge_smallest, defeat_next_fit, defeat_obj, defeat_overload_and_remove,
+    defeat_superfamiconv, defeat_worst_fit, Attack, CombineError, PaletteTooLarge, Strategy,
+};
+
+type Defeat = fn(usize) -> Result<Attack, PaletteTooLarge>;
+
+/// Each strategy's `defeat_*` function, except [`Strategy::Combined`]'s (which takes its components too).
+const DEFEATS: [(Strategy, Defeat); 13] = [
+    (Strategy::AnyFit, defeat_any_fit),
+    (Strategy::BestFusion, defeat_best_fusion),
+    (Strategy::FirstFit, defeat_first_fit),
+    (Strategy::WorstFit, defeat_worst_fit),
+    (Strategy::BestFitDecreasing, defeat_best_fit_decreasing),
+    (Strategy::NextFit, defeat_next_fit),
+    (Strategy::Dsatur, defeat_dsatur),
+    (Strategy::Superfamiconv, defeat_superfamiconv),
+    (Strategy::OverloadAndRemove, defeat_overload_and_remove),
+    (Strategy::MergeSmallest, defeat_merge_smallest),
+    (Strategy::Obj, defeat_obj),
+    (Strategy::FfdIndexTiebreak, defeat_ffd_index_tiebreak),
+    (Strategy::ClusterThenFit, defeat_cluster_then_fit),
+];
+
+#[test]
+fn tiny_sizes_are_rejected() {
+    for palette_size in [0, 1] {
+        for (strategy, defeat) in DEFEATS {
+            let err = defeat(palette_size).unwrap_err();
+            assert_eq!(
+                err,
+                PaletteTooLarge {
+                    strategy,
+                    palette_size
+                }
+            );
+        }
+        assert!(matches!(
+            defeat_combined(&Strategy::COMBINED_DEFAULT, palette_size),
+            Err(CombineError::PaletteTooLarge(_))
+        ));
+    }
+}
+
+#[test]
+fn oversize_sizes_are_rejected() {
+    for (strategy, defeat) in DEFEATS {
+        // Just past the largest size, whether that's odd or even.
+        for palette_size in [
+            strategy.max_palette_size() + 1,
+            strategy.max_palette_size() + 2,
+        ] {
+            let err = defeat(palette_size).unwrap_err();
+            assert_eq!(
+                err,
+                PaletteTooLarge {
+                    strategy,
+                    palette_size
+                }
+            );
+        }
+    }
+    assert!(matches!(
+        defeat_combined(
+            &Strategy::COMBINED_DEFAULT,
+            Strategy::Combined.max_palette_size() + 2
+        ),
+        Err(CombineError::PaletteTooLarge(_))
+    ));
+}
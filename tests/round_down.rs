@@ -0,0 +1,17 @@
+// Checks that odd palette sizes lose their last slot, and that even ones are kept as-is.
+
+use no_packsaran::round_down_to_even;
+
+#[test]
+fn odd_sizes_are_rounded_down() {
+    assert_eq!(round_down_to_even(3), 2);
+    assert_eq!(round_down_to_even(5), 4);
+    assert_eq!(round_down_to_even(7), 6);
+}
+
+#[test]
+fn even_sizes_are_unchanged() {
+    for palette_size in [2, 4, 6, 8, 16, 256] {
+        assert_eq!(round_down_to_even(palette_size), palette_size);
+    }
+}
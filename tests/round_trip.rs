@@ -0,0 +1,83 @@
+// Stores an image, loads it back, and checks that each tile still has exactly its proto-palette's colours.
+
+use std::collections::BTreeSet;
+
+use no_packsaran::{
+    build_palette, defeat_any_fit, gen_image, plumers::prelude::*, PaletteStyle, TileFill,
+};
+
+const COLUMNS: usize = 4;
+
+fn check_round_trip(tile_width: usize, tile_height: usize) {
+    let attack = defeat_any_fit(4).unwrap();
+    let palette = build_palette(attack.nb_colors, PaletteStyle::default());
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("any_fit.png");
+
+    // Tiles too small to hold all of their colours must be refused, rather than silently losing some.
+    let result = gen_image(
+        tile_width,
+        tile_height,
+        COLUMNS,
+        1,
+        &palette,
+        &attack.tiles,
+        TileFill::default(),
+        ImageFormat::Png,
+        path.as_path(),
+    );
+    if attack
+        .tiles
+        .iter()
+        .any(|tile| tile.len() > tile_width * tile_height)
+    {
+        let err = result.unwrap_err();
+        assert_eq!(err.kind(), std::io::ErrorKind::InvalidInput, "{err}");
+        assert!(!path.exists());
+        return;
+    }
+    result.unwrap();
+    let img = PalettedImage32::load(
+        path.as_path(),
+        LoadFlags::default(),
+        AlphaMode::ZeroIsTransparent,
+    )
+    .unwrap();
+
+    // The embedded palette is loaded as-is, so the indices must not have moved.
+    assert_eq!(img.palette(), palette.as_slice());
+    assert_eq!(img.width(), tile_width * COLUMNS);
+    assert_eq!(
+        img.height(),
+        tile_height * attack.tiles.len().div_ceil(COLUMNS)
+    );
+    for (i, tile) in attack.tiles.iter().enumerate() {
+        let (column, row) = (i % COLUMNS, i / COLUMNS);
+        let indices: BTreeSet<u8> = (0..tile_height)
+            .flat_map(|y| (0..tile_width).map(move |x| (x, y)))
+            .map(|(x, y)| *img.index_at(0, column * tile_width + x, row * tile_height + y))
+            .collect();
+        let expected: BTreeSet<u8> = tile.colors().iter().copied().collect();
+        assert_eq!(indices, expected, "Tile #{i} has the wrong colours");
+    }
+}
+
+#[test]
+fn round_trip_8x8() {
+    check_round_trip(8, 8);
+}
+
+#[test]
+fn round_trip_8x16() {
+    check_round_trip(8, 16);
+}
+
+#[test]
+fn round_trip_custom_size() {
+    check_round_trip(3, 2);
+}
+
+#[test]
+fn round_trip_single_pixel() {
+    check_round_trip(1, 1);
+}
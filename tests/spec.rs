@@ -0,0 +1,42 @@
+// Checks that `--from-spec` files describing tiles that can't be drawn, or that aren't valid JSON, are rejected when read.
+
+use no_packsaran::{read_spec_file, SpecFileError};
+
+fn read(contents: &str) -> Result<(), SpecFileError> {
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("spec.json");
+    std::fs::write(&path, contents).unwrap();
+    read_spec_file(&path).map(|_| ())
+}
+
+#[test]
+fn empty_tile_is_rejected() {
+    let err = read(r#"{"nb_colors": 2, "tiles": [[0, 1], []]}"#).unwrap_err();
+    assert!(matches!(err, SpecFileError::EmptyTile(1)), "{err}");
+}
+
+#[test]
+fn out_of_range_color_is_located() {
+    let err = read(r#"{"nb_colors": 2, "tiles": [[0], [1, 2]]}"#).unwrap_err();
+    assert!(
+        matches!(
+            err,
+            SpecFileError::ColorOutOfRange {
+                index: 1,
+                position: 1,
+                color: 2,
+                ..
+            }
+        ),
+        "{err}"
+    );
+}
+
+#[test]
+fn deep_nesting_is_a_syntax_error() {
+    // Enough to overflow the stack, if the parser recursed all the way down.
+    let err = read(&"[".repeat(1_000_000)).unwrap_err();
+    assert!(matches!(err, SpecFileError::Syntax(1)), "{err}");
+    let err = read(r#"{"nb_colors": 2, "tiles": [[[[[[[[0]]]]]]]]}"#).unwrap_err();
+    assert!(matches!(err, SpecFileError::Syntax(1)), "{err}");
+}
@@ -0,0 +1,64 @@
+// Checks the invariants that other strategies build upon, for many palette sizes:
+// tiles have exactly the intended number of colours, all within the palette, and there are as many as documented.
+
+use no_packsaran::{defeat_any_fit, defeat_best_fusion, Attack};
+use proptest::prelude::*;
+
+/// How many ways there are to pick `k` items out of `n`.
+fn binomial(n: usize, k: usize) -> usize {
+    (0..k).fold(1, |acc, i| acc * (n - i) / (i + 1))
+}
+
+/// Checks that every tile has exactly `nb_colors_per_tile` distinct colours, all of them below `nb_colors`.
+fn check_tiles(attack: &Attack, nb_colors_per_tile: usize) -> Result<(), TestCaseError> {
+    for (i, tile) in attack.tiles.iter().enumerate() {
+        let mut colors = tile.colors().to_vec();
+        colors.sort_unstable();
+        colors.dedup();
+        prop_assert_eq!(
+            colors.len(),
+            tile.len(),
+            "Tile #{} has duplicate colours",
+            i
+        );
+        prop_assert_eq!(
+            colors.len(),
+            nb_colors_per_tile,
+            "Tile #{} has the wrong number of colours",
+            i
+        );
+        prop_assert!(
+            colors.iter().all(|&color| color < attack.nb_colors),
+            "Tile #{} uses colours beyond {}",
+            i,
+            attack.nb_colors
+        );
+    }
+    Ok(())
+}
+
+proptest! {
+    // Larger sizes have so many tiles, or take so long to generate, that this would take a while.
+    #[test]
+    fn any_fit_tiles_are_well_formed(half_size in 1usize..=7) {
+        let palette_size = half_size * 2;
+        let attack = defeat_any_fit(palette_size).unwrap();
+
+        check_tiles(&attack, half_size)?;
+        // One pair of tiles per way to pick half of the palette.
+        prop_assert_eq!(attack.tiles.len(), binomial(palette_size, half_size) * 2);
+    }
+
+    #[test]
+    fn best_fusion_tiles_are_well_formed(half_size in 2usize..=8) {
+        let palette_size = half_size * 2;
+        let attack = defeat_best_fusion(palette_size).unwrap();
+
+        check_tiles(&attack, palette_size - 1)?;
+        // Each of the N/2 pairs of (N-1)-combinations makes 4 tiles that fill two palettes, and N-2 more.
+        prop_assert_eq!(
+            attack.tiles.len(),
+            palette_size * 2 + half_size * (palette_size - 2)
+        );
+    }
+}